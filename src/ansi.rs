@@ -0,0 +1,142 @@
+use convert::AsciiArt;
+use palette::Palette;
+
+pub static OUTPUT_FORMAT_NAMES: &[&str] = &["plain", "ansi16", "ansi256", "truecolor"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Plain,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "plain" => Some(OutputFormat::Plain),
+            "ansi16" => Some(OutputFormat::Ansi16),
+            "ansi256" => Some(OutputFormat::Ansi256),
+            "truecolor" => Some(OutputFormat::TrueColor),
+            _ => None,
+        }
+    }
+}
+
+// The SGR parameters selecting a foreground and background color
+fn color_parameters(
+    format: OutputFormat,
+    palette: &Palette,
+    foreground: [u8; 3],
+    background: [u8; 3]
+) -> String {
+    match format {
+        OutputFormat::Plain => String::new(),
+        OutputFormat::Ansi16 => {
+            // 0-7 are 30-37 / 40-47, their bright versions 8-15 are 90-97 / 100-107
+            let sgr = |index: u8, base: u8| {
+                if index < 8 {
+                    base + index
+                } else {
+                    base + 60 + index - 8
+                }
+            };
+            let f = palette.nearest(foreground, 0..16);
+            let b = palette.nearest(background, 0..16);
+            format!("{};{}", sgr(f, 30), sgr(b, 40))
+        }
+        OutputFormat::Ansi256 => {
+            let f = palette.nearest(foreground, 0..=255);
+            let b = palette.nearest(background, 0..=255);
+            format!("38;5;{};48;5;{}", f, b)
+        }
+        OutputFormat::TrueColor => {
+            format!("38;2;{};{};{};48;2;{};{};{}",
+                    foreground[0], foreground[1], foreground[2],
+                    background[0], background[1], background[2])
+        }
+    }
+}
+
+// Escape codes are only emitted when the colors change, and every line ends with a reset.
+// The indexed formats pick from the palette the terminal is assumed to have.
+pub fn render(art: &AsciiArt, format: OutputFormat, palette: &Palette) -> String {
+    if format == OutputFormat::Plain {
+        return art.to_text();
+    }
+
+    let mut text = String::new();
+
+    for row in art.cells.chunks(art.columns as usize) {
+        let mut current = String::new();
+        for cell in row {
+            let parameters = color_parameters(format, palette, cell.foreground, cell.background);
+            if parameters != current {
+                text.push_str("\x1b[");
+                text.push_str(&parameters);
+                text.push('m');
+                current = parameters;
+            }
+            text.push(cell.c);
+        }
+        text.push_str("\x1b[0m\n");
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use convert::Cell;
+    use std::fs::File;
+    use std::io::Read;
+    use tests::fixture;
+
+    // Two rows of three cells, the second row repeating its colors so only one escape is needed
+    fn art() -> AsciiArt {
+        let cell = |c, foreground, background| Cell {
+            c,
+            foreground,
+            background,
+            render: None,
+        };
+        AsciiArt {
+            columns: 3,
+            rows: 2,
+            cells: vec![
+                cell('#', [255, 255, 255], [0, 0, 0]),
+                cell('+', [205, 0, 0], [0, 0, 238]),
+                cell('.', [250, 130, 20], [40, 40, 40]),
+                cell('@', [0, 205, 0], [229, 229, 229]),
+                cell('@', [0, 205, 0], [229, 229, 229]),
+                cell(' ', [0, 205, 0], [229, 229, 229]),
+            ],
+        }
+    }
+
+    // The golden files are compared byte for byte, resets and all
+    fn assert_golden(format: OutputFormat, name: &str) {
+        let mut golden = Vec::new();
+        File::open(fixture(&format!("ansi/{}", name)))
+            .and_then(|mut file| file.read_to_end(&mut golden))
+            .unwrap();
+        let rendered = render(&art(), format, &Palette::default());
+        assert_eq!(rendered.as_bytes(), &golden[..], "{:?}", rendered);
+    }
+
+    #[test]
+    fn ansi16_matches_golden() {
+        assert_golden(OutputFormat::Ansi16, "ansi16.txt");
+    }
+
+    #[test]
+    fn ansi256_matches_golden() {
+        assert_golden(OutputFormat::Ansi256, "ansi256.txt");
+    }
+
+    #[test]
+    fn truecolor_matches_golden() {
+        assert_golden(OutputFormat::TrueColor, "truecolor.txt");
+    }
+}
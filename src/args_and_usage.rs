@@ -1,50 +1,125 @@
-use clap::{Arg, ArgGroup, App};
-use glob::glob;
-use std::env;
+use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
 use std::path::PathBuf;
-use std::process::exit;
 use tempdir::TempDir;
+use tracii::{char_sets, default_cache_dir, face_index, find_font, font_directories, terminal_size};
+use tracii::{FontSource, FontStyle, Mode, OutputFormat, Overflow, Palette, SheetLayout, SheetOptions, SizePolicy};
+use tracii::{FONT_STYLE_NAMES, METRIC_NAMES, MODE_NAMES, OUTPUT_FORMAT_NAMES, OVERFLOW_NAMES};
+use tracii::{SHEET_LAYOUT_NAMES, SIZE_POLICY_NAMES, THEME_NAMES};
+use tracii::{Result, TraciiError};
 
 // Programmer defined constants
-static PROGRAM_NAME: &'static str = "tracii";
+static PROGRAM_NAME: &str = "tracii";
 
 // Derived constants
-static VERSION: &'static str = env!("CARGO_PKG_VERSION");
+static VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Converting is the default, the fonts subcommand only inspects what is installed
+pub enum Command {
+    Run(Box<Args>),
+    ListFonts(Option<String>),
+    ListFaces(PathBuf),
+}
 
 pub struct Args {
-    pub cell_ratio: f32,
+    pub cell_ratio: Option<f32>,
+    pub overflow: Overflow,
     pub font_path: PathBuf,
+    pub font_index: Option<usize>,
+    pub fallback_fonts: Vec<FontSource>,
     pub work_dir: PathBuf,
     pub export_glyph_renders: bool,
     pub limited_char_set: bool,
+    pub chars: Option<Vec<char>>,
     pub color_256: bool,
+    pub palette: Palette,
+    pub palette_indices: Vec<u8>,
+    pub min_contrast: f32,
     pub make_render_scramble: bool,
+    pub sheet: SheetOptions,
+    pub seed: u64,
+    pub input: Option<PathBuf>,
+    pub columns: Option<u32>,
+    pub rows: Option<u32>,
+    pub size_policy: SizePolicy,
+    pub mode: Mode,
+    pub metric: String,
+    pub indexed: bool,
+    pub benchmark_index: bool,
+    pub cache_dir: PathBuf,
+    pub rebuild_atlas: bool,
+    pub output_format: OutputFormat,
+    pub export_html: bool,
+    pub export_svg: bool,
+}
+
+// An RRGGBB hex color, with or without a leading #
+fn parse_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok();
+    Some([channel(0)?, channel(1)?, channel(2)?])
 }
 
-pub fn parse_args() -> Args {
+pub fn parse_args() -> Result<Command> {
     let args = App::new(PROGRAM_NAME)
         .version(VERSION)
         .author("Russell W. Bentley <russell.w.bentley@icloud.com>")
         .about("A tool for generating fancy ASCII art")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("FONT_FILE")
             .help("Font file to use for rendering")
             .long("fontfile")
             .value_name("font/path")
             .takes_value(true))
         .arg(Arg::with_name("FONT_NAME")
-            .help("The name of a font to use")
+            .help("The family or full name of an installed font")
             .long("fontname")
             .value_name("name")
             .takes_value(true))
+        .arg(Arg::with_name("FONT_STYLE")
+            .help("Which face of a --fontname family to use")
+            .long("fontstyle")
+            .value_name("style")
+            .possible_values(FONT_STYLE_NAMES)
+            .requires("FONT_NAME"))
+        .arg(Arg::with_name("FONT_INDEX")
+            .help("Which face of a --fontfile collection to use, counting from 0")
+            .long("fontindex")
+            .value_name("N")
+            .takes_value(true)
+            .requires("FONT_FILE"))
+        .arg(Arg::with_name("FONT_FACE")
+            .help("The full name of the face of a --fontfile collection to use")
+            .long("fontface")
+            .value_name("name")
+            .takes_value(true)
+            .requires("FONT_FILE")
+            .conflicts_with("FONT_INDEX"))
+        .arg(Arg::with_name("FALLBACK_FONT")
+            .help("A font file for chars the main font lacks, tried in the order given. \
+                   Each face of a collection is tried in turn.")
+            .long("fallback-font")
+            .value_name("font/path")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
         .group(ArgGroup::with_name("FONT")
             .arg("FONT_FILE")
             .arg("FONT_NAME")
             .required(true))
         .arg(Arg::with_name("RATIO")
-            .help("The height to width ratio of a glyph cell")
+            .help("The height to width ratio of a glyph cell, by default the font's own")
             .long("cellratio")
             .value_name("h/w")
             .takes_value(true))
+        .arg(Arg::with_name("OVERFLOW")
+            .help("What to do with glyphs that reach outside their cell")
+            .long("overflow")
+            .value_name("policy")
+            .possible_values(OVERFLOW_NAMES)
+            .default_value("clip"))
         .arg(Arg::with_name("WORKING_DIRECTORY")
             .help("If you are interested in browsing artifacts, you should pass this")
             .long("workdir")
@@ -56,138 +131,490 @@ pub fn parse_args() -> Args {
         .arg(Arg::with_name("LIMITED_CHARS")
             .help("Use a limited char set")
             .long("limcharset"))
+        .arg(Arg::with_name("CHAR_SET")
+            .help("The chars to render with, repeat to combine sets: ascii, \
+                   ascii-printable-with-space, blocks, box-drawing, braille, latin1, \
+                   custom:<string> or file:<path>")
+            .long("charset")
+            .value_name("set")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .conflicts_with("LIMITED_CHARS"))
         .arg(Arg::with_name("256_COLOR")
             .help("Use all 256 8-bit colors")
             .long("256color"))
+        .arg(Arg::with_name("PALETTE_THEME")
+            .help("The terminal theme whose 16 system colors the palette starts with, by default xterm's")
+            .long("palette-theme")
+            .value_name("theme")
+            .possible_values(THEME_NAMES))
+        .arg(Arg::with_name("PALETTE_FILE")
+            .help("Take the 16 system colors from an .itermcolors, an Alacritty .yml or .toml, or Xresources")
+            .long("palette-file")
+            .value_name("path/to")
+            .takes_value(true)
+            .conflicts_with("PALETTE_THEME"))
+        .arg(Arg::with_name("PALETTE_RANGE")
+            .help("Only sweep the 8-bit colors from the first index to the last")
+            .long("palette-range")
+            .value_name("first-last")
+            .takes_value(true)
+            .requires("256_COLOR"))
+        .arg(Arg::with_name("PALETTE_INDICES")
+            .help("Only sweep these 8-bit colors")
+            .long("palette-indices")
+            .value_name("i,j,...")
+            .takes_value(true)
+            .requires("256_COLOR")
+            .conflicts_with("PALETTE_RANGE"))
+        .arg(Arg::with_name("MIN_CONTRAST")
+            .help("Leave out color pairs whose WCAG contrast ratio, from 1 to 21, is below this, \
+                   by default none are")
+            .long("min-contrast")
+            .value_name("ratio")
+            .takes_value(true)
+            .requires("256_COLOR"))
         .arg(Arg::with_name("RENDER_SCRAMBLE")
             .help("Export a scramble of the glyph renders")
             .long("makerenderscramble"))
+        .arg(Arg::with_name("SHEET_GRID")
+            .help("How many renders across and down the scramble holds")
+            .long("sheet-grid")
+            .value_name("COLUMNSxROWS")
+            .default_value("10x5"))
+        .arg(Arg::with_name("SHEET_PADDING")
+            .help("Pixels of gutter around and between the renders of the scramble")
+            .long("sheet-padding")
+            .value_name("pixels")
+            .default_value("0"))
+        .arg(Arg::with_name("SHEET_GUTTER")
+            .help("The color of the scramble's gutter")
+            .long("sheet-gutter")
+            .value_name("RRGGBB")
+            .default_value("000000"))
+        .arg(Arg::with_name("SHEET_LABELS")
+            .help("Label each render of the scramble with its code point and colors")
+            .long("sheet-labels"))
+        .arg(Arg::with_name("SHEET_LAYOUT")
+            .help("Lay the scramble's renders out in atlas order or shuffled")
+            .long("sheet-layout")
+            .value_name("layout")
+            .possible_values(SHEET_LAYOUT_NAMES)
+            .default_value("shuffled"))
+        .arg(Arg::with_name("SEED")
+            .help("Seeds every random choice, the same seed always makes the same artifacts")
+            .long("seed")
+            .value_name("N")
+            .default_value("0"))
+        .arg(Arg::with_name("INPUT")
+            .help("An image to convert to ASCII art")
+            .long("input")
+            .value_name("image/path")
+            .takes_value(true))
+        .arg(Arg::with_name("COLUMNS")
+            .help("How many cells wide the output is, by default 80 unless --rows decides it, \
+                   and never more than the input is pixels wide")
+            .long("columns")
+            .value_name("N")
+            .takes_value(true))
+        .arg(Arg::with_name("ROWS")
+            .help("How many cells tall the output is, by default whatever --columns makes it, \
+                   and never more than the input is pixels tall")
+            .long("rows")
+            .value_name("N")
+            .takes_value(true)
+            .requires("INPUT"))
+        .arg(Arg::with_name("FIT")
+            .help("Size the output to the terminal, asking the tty or else $COLUMNS and $LINES")
+            .long("fit")
+            .conflicts_with_all(&["COLUMNS", "ROWS"])
+            .requires("INPUT"))
+        .arg(Arg::with_name("SIZE_POLICY")
+            .help("How the input fits when both the columns and rows are fixed, by default pad")
+            .long("size-policy")
+            .value_name("policy")
+            .possible_values(SIZE_POLICY_NAMES)
+            .requires("INPUT"))
+        .arg(Arg::with_name("MODE")
+            .help("Match glyphs to each cell, or draw each cell with braille dots, half or quadrant blocks, \
+                   by default glyphs")
+            .long("mode")
+            .value_name("mode")
+            .possible_values(MODE_NAMES))
+        .arg(Arg::with_name("METRIC")
+            .help("How tiles of the input are matched against glyph renders, by default ssd")
+            .long("metric")
+            .value_name("metric")
+            .possible_values(METRIC_NAMES)
+            .requires("INPUT"))
+        .arg(Arg::with_name("INDEXED")
+            .help("Only score the renders a k-d tree of glyph features puts nearest each tile")
+            .long("indexed")
+            .requires("INPUT"))
+        .arg(Arg::with_name("BENCHMARK_INDEX")
+            .help("Time exhaustive against indexed glyph lookup on a fixed test image")
+            .long("benchindex"))
+        .arg(Arg::with_name("CACHE_DIRECTORY")
+            .help("Where rasterized glyph atlases are kept between runs, the 32 most recently made")
+            .long("cachedir")
+            .value_name("path/to")
+            .takes_value(true))
+        .arg(Arg::with_name("REBUILD_ATLAS")
+            .help("Rasterize the glyph atlas even if a cached one matches")
+            .long("rebuild-atlas"))
+        .arg(Arg::with_name("OUTPUT_FORMAT")
+            .help("How the converted input is written to the terminal, by default plain")
+            .long("output-format")
+            .value_name("format")
+            .possible_values(OUTPUT_FORMAT_NAMES)
+            .requires("INPUT"))
+        .arg(Arg::with_name("EXPORT_HTML")
+            .help("Export the converted input to WORKDIR/ascii.html")
+            .long("exporthtml")
+            .requires("INPUT"))
+        .arg(Arg::with_name("EXPORT_SVG")
+            .help("Export the converted input to WORKDIR/ascii.svg")
+            .long("exportsvg")
+            .requires("INPUT"))
+        .subcommand(SubCommand::with_name("fonts")
+            .about("Inspect the installed fonts")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .about("List every installed monospace font with its family, style and path")
+                .arg(Arg::with_name("PATTERN")
+                    .help("Only list fonts whose name contains this")
+                    .index(1)))
+            .subcommand(SubCommand::with_name("faces")
+                .about("List the faces in a font file, collections have more than one")
+                .arg(Arg::with_name("FILE")
+                    .help("The font file to look inside")
+                    .required(true)
+                    .index(1))))
         .get_matches();
 
-    // The cell_ratio is a float parsed from a str with a default of 1.9
-    // exit on a parse error
+    if let Some(fonts) = args.subcommand_matches("fonts") {
+        if let Some(list) = fonts.subcommand_matches("list") {
+            return Ok(Command::ListFonts(list.value_of("PATTERN").map(String::from)));
+        }
+        if let Some(faces) = fonts.subcommand_matches("faces") {
+            let path = PathBuf::from(faces.value_of("FILE").unwrap_or_default());
+            if !path.exists() {
+                return Err(TraciiError::PathNotFound(path));
+            }
+            return Ok(Command::ListFaces(path));
+        }
+    }
+
+    // The cell_ratio is a float parsed from a str, without one the font decides.
+    // Cells are sized by dividing by it, so it must be a finite number above zero.
     let cell_ratio = match args.value_of("RATIO") {
         Some(ratio_str) => {
-            match ratio_str.parse() {
-                Ok(ratio) => ratio,
+            match ratio_str.parse::<f32>() {
+                Ok(ratio) if ratio.is_finite() && ratio > 0.0 => Some(ratio),
+                Ok(_) => {
+                    return Err(TraciiError::InvalidArgument(format!(
+                        "--cellratio / -r must be a finite number above 0, not {}\n\n{}",
+                        ratio_str,
+                        args.usage()
+                    )));
+                }
                 Err(parse_error) => {
-                    println!("--cellratio / -r must be parsable as an f32");
-                    println!("Attempting to parse {} gave the following error:\n{}",
-                             ratio_str,
-                             parse_error);
-                    println!("\n{}", args.usage());
-                    exit(1)
+                    return Err(TraciiError::InvalidArgument(format!(
+                        "--cellratio / -r must be parsable as an f32\n\
+                         Attempting to parse {} gave the following error:\n{}\n\n{}",
+                        ratio_str,
+                        parse_error,
+                        args.usage()
+                    )));
                 }
             }
         }
-        None => 1.9f32,
+        None => None,
     };
 
-    // We are either passed a name or a file
-    let font_path = match (args.value_of("FONT_FILE"), args.value_of("FONT_NAME")) {
+    // A count of cells must be a whole number above zero
+    let cell_count = |name: &str, flag: &str| -> Result<Option<u32>> {
+        match args.value_of(name) {
+            Some(count_str) => match count_str.parse::<u32>() {
+                Ok(count) if count > 0 => Ok(Some(count)),
+                _ => Err(TraciiError::InvalidArgument(format!(
+                    "{} must be a whole number of cells above 0, not {}\n\n{}",
+                    flag,
+                    count_str,
+                    args.usage()
+                ))),
+            },
+            None => Ok(None),
+        }
+    };
+
+    // The flags that shape more than the conversion still need a stage that uses them
+    let needs_a_stage = |name: &str, flag: &str, stages: &[(&str, &str)]| -> Result<()> {
+        if !args.is_present(name) || stages.iter().any(|&(stage, _)| args.is_present(stage)) {
+            return Ok(());
+        }
+        let flags: Vec<&str> = stages.iter().map(|&(_, flag)| flag).collect();
+        Err(TraciiError::InvalidArgument(format!(
+            "{} only applies with {}\n\n{}",
+            flag,
+            flags.join(" or "),
+            args.usage()
+        )))
+    };
+    needs_a_stage("COLUMNS", "--columns", &[("INPUT", "--input"), ("BENCHMARK_INDEX", "--benchindex")])?;
+    needs_a_stage("MODE", "--mode", &[
+        ("INPUT", "--input"),
+        ("EXPORT_GLYPHS", "--exportglyphs"),
+        ("BENCHMARK_INDEX", "--benchindex"),
+        ("RENDER_SCRAMBLE", "--makerenderscramble"),
+    ])?;
+
+    // The last line of the terminal is left for the prompt that follows the output
+    let (columns, rows) = if args.is_present("FIT") {
+        match terminal_size() {
+            Some((columns, rows)) => (Some(columns), Some(rows.saturating_sub(1).max(1))),
+            None => {
+                return Err(TraciiError::InvalidArgument(String::from(
+                    "--fit needs a terminal, or $COLUMNS and $LINES to be set"
+                )));
+            }
+        }
+    } else {
+        (cell_count("COLUMNS", "--columns")?, cell_count("ROWS", "--rows")?)
+    };
+
+    let sheet_grid = args.value_of("SHEET_GRID").unwrap_or_default();
+    let (sheet_columns, sheet_rows) = match sheet_grid.split_once('x') {
+        Some((columns, rows)) => match (columns.parse(), rows.parse()) {
+            (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => (columns, rows),
+            _ => (0, 0),
+        },
+        None => (0, 0),
+    };
+    if sheet_columns == 0 {
+        return Err(TraciiError::InvalidArgument(format!(
+            "--sheet-grid must be two whole numbers above 0 like 10x5, not {}\n\n{}",
+            sheet_grid,
+            args.usage()
+        )));
+    }
+
+    let sheet_padding = args.value_of("SHEET_PADDING").unwrap_or_default();
+    let sheet_padding = sheet_padding.parse().map_err(|parse_error| {
+        TraciiError::InvalidArgument(format!(
+            "--sheet-padding must be parsable as a u32\n\
+             Attempting to parse {} gave the following error:\n{}\n\n{}",
+            sheet_padding,
+            parse_error,
+            args.usage()
+        ))
+    })?;
+
+    let sheet_gutter = args.value_of("SHEET_GUTTER").unwrap_or_default();
+    let sheet_gutter = parse_color(sheet_gutter).ok_or_else(|| {
+        TraciiError::InvalidArgument(format!(
+            "--sheet-gutter must be a hex color like 1d1f21, not {}\n\n{}",
+            sheet_gutter,
+            args.usage()
+        ))
+    })?;
+
+    let palette = match (args.value_of("PALETTE_THEME"), args.value_of("PALETTE_FILE")) {
+        (_, Some(palette_file)) => {
+            let path = PathBuf::from(palette_file);
+            if !path.exists() {
+                return Err(TraciiError::PathNotFound(path));
+            }
+            Palette::load(&path)?
+        }
+        (Some(theme), None) => Palette::theme(theme).unwrap_or_default(),
+        (None, None) => Palette::default(),
+    };
+
+    // Palette indices run from 0 to 255, a range includes both of its ends
+    let parse_index = |index_str: &str, flag: &str| -> Result<u8> {
+        index_str.trim().parse().map_err(|_| {
+            TraciiError::InvalidArgument(format!(
+                "{} takes palette indices from 0 to 255, not {}\n\n{}",
+                flag,
+                index_str,
+                args.usage()
+            ))
+        })
+    };
+    let palette_indices = match (args.value_of("PALETTE_RANGE"), args.value_of("PALETTE_INDICES")) {
+        (Some(range), _) => match range.split_once('-') {
+            Some((first, last)) => {
+                let first = parse_index(first, "--palette-range")?;
+                let last = parse_index(last, "--palette-range")?;
+                if first > last {
+                    return Err(TraciiError::InvalidArgument(format!(
+                        "--palette-range must not end before it starts, {} is after {}\n\n{}",
+                        first,
+                        last,
+                        args.usage()
+                    )));
+                }
+                (first..=last).collect()
+            }
+            None => {
+                return Err(TraciiError::InvalidArgument(format!(
+                    "--palette-range must be two indices like 16-231, not {}\n\n{}",
+                    range,
+                    args.usage()
+                )));
+            }
+        },
+        (None, Some(indices)) => indices
+            .split(',')
+            .map(|index| parse_index(index, "--palette-indices"))
+            .collect::<Result<Vec<u8>>>()?,
+        (None, None) => (0..=255).collect(),
+    };
+
+    // A default would count as passing the flag, which requires --256color
+    let min_contrast = args.value_of("MIN_CONTRAST").unwrap_or("1");
+    let min_contrast = min_contrast.parse().map_err(|parse_error| {
+        TraciiError::InvalidArgument(format!(
+            "--min-contrast must be parsable as an f32\n\
+             Attempting to parse {} gave the following error:\n{}\n\n{}",
+            min_contrast,
+            parse_error,
+            args.usage()
+        ))
+    })?;
+
+    let seed = args.value_of("SEED").unwrap_or_default();
+    let seed = seed.parse().map_err(|parse_error| {
+        TraciiError::InvalidArgument(format!(
+            "--seed must be parsable as a u64\n\
+             Attempting to parse {} gave the following error:\n{}\n\n{}",
+            seed,
+            parse_error,
+            args.usage()
+        ))
+    })?;
+
+    let sheet = SheetOptions {
+        columns: sheet_columns,
+        rows: sheet_rows,
+        padding: sheet_padding,
+        gutter: sheet_gutter,
+        labels: args.is_present("SHEET_LABELS"),
+        layout: args.value_of("SHEET_LAYOUT")
+            .and_then(SheetLayout::from_name)
+            .unwrap_or(SheetLayout::Shuffled),
+    };
+
+    // We are either passed a name or a file, and maybe which face of a file
+    let (font_path, font_index) = match (args.value_of("FONT_FILE"), args.value_of("FONT_NAME")) {
         (Some(file_path_str), None) => {
             let path = PathBuf::from(file_path_str);
             if !path.exists() {
-                println!("{} does not exist", path.to_string_lossy());
-                exit(1);
+                return Err(TraciiError::PathNotFound(path));
             }
-            path
+            let index = match (args.value_of("FONT_INDEX"), args.value_of("FONT_FACE")) {
+                (Some(index_str), _) => Some(index_str.parse().map_err(|parse_error| {
+                    TraciiError::InvalidArgument(format!(
+                        "--fontindex must be parsable as a usize\n\
+                         Attempting to parse {} gave the following error:\n{}\n\n{}",
+                        index_str,
+                        parse_error,
+                        args.usage()
+                    ))
+                })?),
+                (None, Some(face_name)) => Some(face_index(&path, face_name)?),
+                (None, None) => None,
+            };
+            (path, index)
+        }
+        (None, Some(font_name_str)) => {
+            let style = args.value_of("FONT_STYLE").and_then(FontStyle::from_name);
+            let (path, index) = find_font(font_name_str, style, &font_directories())?;
+            (path, Some(index))
         }
-        (None, Some(font_name_str)) => find_font(font_name_str),
         _ => {
-            println!("Either both --fontfile and --fontname were passed or neither.");
-            println!("It shouldn't be possible to see this! File a bug!");
-            exit(1)
+            return Err(TraciiError::InvalidArgument(String::from(
+                "Either both --fontfile and --fontname were passed or neither.\n\
+                 It shouldn't be possible to see this! File a bug!"
+            )));
         }
     };
 
+    let chars = match args.values_of("CHAR_SET") {
+        Some(specs) => Some(char_sets(&specs.collect::<Vec<_>>())?),
+        None => None,
+    };
+
+    let mut fallback_fonts = Vec::new();
+    for fallback in args.values_of("FALLBACK_FONT").into_iter().flatten() {
+        let path = PathBuf::from(fallback);
+        if !path.exists() {
+            return Err(TraciiError::PathNotFound(path));
+        }
+        fallback_fonts.extend(FontSource::faces(&path)?);
+    }
+
     let work_dir = match args.value_of("WORKING_DIRECTORY") {
         Some(work_dir) => {
             let path = PathBuf::from(work_dir);
             if !path.exists() {
-                println!("{} does not exist", path.to_string_lossy());
-                exit(1);
+                return Err(TraciiError::PathNotFound(path));
             }
             path
         }
-        None => {
-            let tempdir = match TempDir::new("tracii") {
-                Ok(dir) => dir,
-                Err(error) => {
-                    println!("There was an error making a temporary directory:\n{}",
-                             error);
-                    exit(1);
-                }
-            };
-            tempdir.into_path()
+        None => TempDir::new("tracii").map_err(TraciiError::WorkDir)?.into_path(),
+    };
+
+    let input = match args.value_of("INPUT") {
+        Some(input) => {
+            let path = PathBuf::from(input);
+            if !path.exists() {
+                return Err(TraciiError::PathNotFound(path));
+            }
+            Some(path)
         }
+        None => None,
     };
 
-    Args {
-        cell_ratio: cell_ratio,
-        font_path: font_path,
-        work_dir: work_dir,
+    // Without a usable home directory the atlas is cached alongside the other artifacts
+    let cache_dir = match args.value_of("CACHE_DIRECTORY") {
+        Some(cache_dir) => PathBuf::from(cache_dir),
+        None => default_cache_dir().unwrap_or_else(|| work_dir.join("cache")),
+    };
+
+    Ok(Command::Run(Box::new(Args {
+        cell_ratio,
+        overflow: args.value_of("OVERFLOW").and_then(Overflow::from_name).unwrap_or(Overflow::Clip),
+        font_path,
+        font_index,
+        fallback_fonts,
+        work_dir,
         export_glyph_renders: args.is_present("EXPORT_GLYPHS"),
         limited_char_set: args.is_present("LIMITED_CHARS"),
+        chars,
         color_256: args.is_present("256_COLOR"),
+        palette,
+        palette_indices,
+        min_contrast,
         make_render_scramble: args.is_present("RENDER_SCRAMBLE"),
-    }
-}
-
-fn find_font(name: &str) -> PathBuf {
-    // Font directories - places to check
-    // https://support.apple.com/en-us/HT201722
-    let mut font_directories = vec![
-        String::from("/Library/Fonts/"),
-        String::from("/Network/Library/Fonts/"),
-        String::from("/System/Library/Fonts/"),
-        String::from("/System Folder/Fonts/")
-    ];
-
-    if let Ok(path) = env::var("HOME") {
-        let user_home_font = path + "/Library/Fonts/";
-        font_directories.push(user_home_font);
-    }
-
-    let mut candidates: Vec<PathBuf> = Vec::new();
-
-    for directory in font_directories {
-        let pattern = directory + name + "*";
-        let paths = match glob(&pattern) {
-            Ok(paths) => paths,
-            Err(pattern_error) => {
-                println!("There was a pattern error while searching for the font name {}:",
-                         name);
-                println!("{}", pattern_error);
-                exit(1)
-            }
-        };
-
-        for path in paths {
-            match path {
-                Ok(path) => candidates.push(path),
-                Err(glob_error) => {
-                    println!("There was a glob error:\n{}", glob_error);
-                    exit(1);
-                }
-            }
-        }
-    }
-
-    if candidates.len() == 0 {
-        println!("Unable to locate a font with the name {}", name);
-        exit(1);
-    }
-
-    if candidates.len() > 1 {
-        println!("We found the the following font files that matched {}:\n",
-                 name);
-        for candidate in candidates {
-            println!("\t{}", candidate.to_string_lossy());
-        }
-        println!("\nThere can only be one viable file");
-        exit(1);
-    }
-
-    candidates.remove(0)
+        sheet,
+        seed,
+        input,
+        columns,
+        rows,
+        size_policy: args.value_of("SIZE_POLICY").and_then(SizePolicy::from_name).unwrap_or(SizePolicy::Pad),
+        mode: args.value_of("MODE").and_then(Mode::from_name).unwrap_or(Mode::Glyphs),
+        metric: String::from(args.value_of("METRIC").unwrap_or("ssd")),
+        indexed: args.is_present("INDEXED"),
+        benchmark_index: args.is_present("BENCHMARK_INDEX"),
+        cache_dir,
+        rebuild_atlas: args.is_present("REBUILD_ATLAS"),
+        output_format: args.value_of("OUTPUT_FORMAT")
+            .and_then(OutputFormat::from_name)
+            .unwrap_or(OutputFormat::Plain),
+        export_html: args.is_present("EXPORT_HTML"),
+        export_svg: args.is_present("EXPORT_SVG"),
+    })))
 }
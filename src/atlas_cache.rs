@@ -0,0 +1,349 @@
+use error::{Result, TraciiError};
+use render_glyphs::{FontSource, GlyphMask, Overflow};
+use std::fs::{self, create_dir_all, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+// Bump this whenever the layout below changes, old atlases are then ignored
+static ATLAS_VERSION: u32 = 4;
+static ATLAS_MAGIC: &[u8; 8] = b"TRACIIAT";
+// The magic, version, hash, count, width and height
+static HEADER_LENGTH: u64 = 8 + 4 + 8 + 4 + 4 + 4;
+
+// Every input size, font and char set gets its own atlas, so only the most recently
+// written ones are kept
+static MAX_ATLASES: usize = 32;
+
+// Everything that goes into rasterizing an atlas. If any of it changes, so does the hash.
+// The palette is not part of it, the atlas only holds coverage masks.
+pub struct AtlasKey {
+    // The hash and face index of every font in the chain, primary first
+    fonts: Vec<(u64, usize)>,
+    chars: Vec<char>,
+    cell_width: u32,
+    cell_height: u32,
+    overflow: Overflow,
+}
+
+// FNV-1a, which unlike the std hasher is stable between builds
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+impl AtlasKey {
+    pub fn new(
+        fonts: &[FontSource],
+        chars: &[char],
+        cell_size: (u32, u32),
+        overflow: Overflow
+    ) -> Result<AtlasKey> {
+        let mut font_hashes = Vec::new();
+        for font in fonts {
+            let mut font_bytes = Vec::new();
+            File::open(&font.path)
+                .and_then(|mut file| file.read_to_end(&mut font_bytes))
+                .map_err(|error| TraciiError::Io { path: font.path.clone(), error })?;
+
+            let mut font_hash = Fnv::new();
+            font_hash.write(&font_bytes);
+            font_hashes.push((font_hash.0, font.index.unwrap_or(0)));
+        }
+
+        Ok(AtlasKey {
+            fonts: font_hashes,
+            chars: chars.to_vec(),
+            cell_width: cell_size.0,
+            cell_height: cell_size.1,
+            overflow,
+        })
+    }
+
+    pub fn hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(&ATLAS_VERSION.to_le_bytes());
+        for &(font_hash, font_index) in &self.fonts {
+            hash.write(&font_hash.to_le_bytes());
+            hash.write(&(font_index as u64).to_le_bytes());
+        }
+        for c in &self.chars {
+            hash.write(&(*c as u32).to_le_bytes());
+        }
+        hash.write(&self.cell_width.to_le_bytes());
+        hash.write(&self.cell_height.to_le_bytes());
+        hash.write(&[self.overflow as u8]);
+        hash.0
+    }
+}
+
+// Where atlases live unless --cachedir says otherwise
+pub fn default_cache_dir() -> Option<PathBuf> {
+    if let Some(cache_home) = ::std::env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(cache_home).join("tracii"));
+    }
+
+    ::std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("tracii"))
+}
+
+pub fn atlas_path(cache_dir: &Path, key: &AtlasKey) -> PathBuf {
+    cache_dir.join(format!("atlas-{:016x}.bin", key.hash()))
+}
+
+fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// Returns None when there is no usable atlas, in which case the caller should rasterize
+pub fn load(path: &Path, key: &AtlasKey) -> Option<Vec<GlyphMask>> {
+    let file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    match read_atlas(&mut BufReader::new(file), length, key) {
+        Ok(masks) => masks,
+        Err(error) => {
+            eprintln!("WARN: Ignoring the unreadable atlas {}:\n{}",
+                      path.to_string_lossy(),
+                      error);
+            None
+        }
+    }
+}
+
+// An atlas whose header disagrees with the key or the file's length is a miss, it is never
+// trusted with an allocation
+fn read_atlas(reader: &mut dyn Read, length: u64, key: &AtlasKey) -> io::Result<Option<Vec<GlyphMask>>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != ATLAS_MAGIC
+        || read_u32(reader)? != ATLAS_VERSION
+        || read_u64(reader)? != key.hash() {
+        return Ok(None);
+    }
+
+    let count = read_u32(reader)?;
+    let width = read_u32(reader)?;
+    let height = read_u32(reader)?;
+
+    // Each mask is its char, its source and a coverage value a pixel
+    let mask_length = 8 + 4 * u64::from(width) * u64::from(height);
+    let fits_key = count as usize <= key.chars.len()
+        && (count == 0 || (width, height) == (key.cell_width, key.cell_height));
+    if !fits_key || HEADER_LENGTH + u64::from(count) * mask_length != length {
+        return Ok(None);
+    }
+
+    let mut masks = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let c = match ::std::char::from_u32(read_u32(reader)?) {
+            Some(c) => c,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid char")),
+        };
+        let source = read_u32(reader)? as usize;
+
+        let mut coverage = Vec::with_capacity((width * height) as usize);
+        for _ in 0..width * height {
+            coverage.push(f32::from_bits(read_u32(reader)?));
+        }
+
+        masks.push(GlyphMask {
+            coverage,
+            width,
+            height,
+            c,
+            source,
+        });
+    }
+
+    Ok(Some(masks))
+}
+
+// A failure to cache is not fatal, the next run just rasterizes again. The atlas is written
+// beside its path and renamed into place, so a run never reads one half written.
+pub fn save(path: &Path, key: &AtlasKey, masks: &[GlyphMask]) {
+    let partial_path = path.with_extension(format!("{}.partial", process::id()));
+    let result = path
+        .parent()
+        .map_or(Ok(()), create_dir_all)
+        .and_then(|_| File::create(&partial_path))
+        .and_then(|file| write_atlas(&mut BufWriter::new(file), key, masks))
+        .and_then(|_| fs::rename(&partial_path, path));
+
+    if let Err(error) = result {
+        let _ = fs::remove_file(&partial_path);
+        eprintln!("WARN: There was an error caching the atlas to {}:\n{}",
+                  path.to_string_lossy(),
+                  error);
+    }
+
+    if let Some(cache_dir) = path.parent() {
+        prune(cache_dir, MAX_ATLASES);
+    }
+}
+
+// Delete all but the newest keep atlases in the cache directory
+fn prune(cache_dir: &Path, keep: usize) {
+    let entries = match fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut atlases: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with("atlas-") && name.ends_with(".bin")
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    atlases.sort_by(|a, b| b.cmp(a));
+
+    for (_, path) in atlases.into_iter().skip(keep) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn write_atlas(writer: &mut dyn Write, key: &AtlasKey, masks: &[GlyphMask]) -> io::Result<()> {
+    let (width, height) = masks
+        .first()
+        .map_or((0, 0), |mask| (mask.width, mask.height));
+
+    writer.write_all(ATLAS_MAGIC)?;
+    writer.write_all(&ATLAS_VERSION.to_le_bytes())?;
+    writer.write_all(&key.hash().to_le_bytes())?;
+    writer.write_all(&(masks.len() as u32).to_le_bytes())?;
+    writer.write_all(&width.to_le_bytes())?;
+    writer.write_all(&height.to_le_bytes())?;
+
+    for mask in masks {
+        writer.write_all(&(mask.c as u32).to_le_bytes())?;
+        writer.write_all(&(mask.source as u32).to_le_bytes())?;
+        for value in &mask.coverage {
+            writer.write_all(&value.to_bits().to_le_bytes())?;
+        }
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempdir::TempDir;
+    use tests::fixture;
+
+    fn key(cell_size: (u32, u32)) -> AtlasKey {
+        let fonts = [FontSource {
+            path: fixture("DejaVuSansMono.ttf"),
+            index: None,
+        }];
+        AtlasKey::new(&fonts, &['a', 'b'], cell_size, Overflow::Clip).unwrap()
+    }
+
+    fn masks(cell_size: (u32, u32)) -> Vec<GlyphMask> {
+        "ab".chars()
+            .map(|c| GlyphMask {
+                coverage: vec![0.5; (cell_size.0 * cell_size.1) as usize],
+                width: cell_size.0,
+                height: cell_size.1,
+                c,
+                source: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saved_atlases_load_back() {
+        let dir = TempDir::new("tracii").unwrap();
+        let key = key((2, 4));
+        let path = atlas_path(dir.path(), &key);
+        save(&path, &key, &masks((2, 4)));
+
+        let loaded = load(&path, &key).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!((loaded[1].c, loaded[1].width, loaded[1].height), ('b', 2, 4));
+        assert_eq!(loaded[1].coverage, vec![0.5; 8]);
+
+        // Nothing but the atlas is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn truncated_atlases_are_misses() {
+        let dir = TempDir::new("tracii").unwrap();
+        let key = key((2, 4));
+        let path = atlas_path(dir.path(), &key);
+        save(&path, &key, &masks((2, 4)));
+
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 4).unwrap();
+        assert!(load(&path, &key).is_none());
+    }
+
+    #[test]
+    fn atlases_of_another_cell_size_are_misses() {
+        let dir = TempDir::new("tracii").unwrap();
+        let key = key((2, 4));
+        let path = atlas_path(dir.path(), &key);
+        // The header claims a bigger cell than the key was made for
+        save(&path, &key, &masks((3, 4)));
+        assert!(load(&path, &key).is_none());
+    }
+
+    #[test]
+    fn absurd_counts_are_misses() {
+        let dir = TempDir::new("tracii").unwrap();
+        let key = key((2, 4));
+        let path = atlas_path(dir.path(), &key);
+
+        let mut file = File::create(&path).unwrap();
+        file.write_all(ATLAS_MAGIC).unwrap();
+        file.write_all(&ATLAS_VERSION.to_le_bytes()).unwrap();
+        file.write_all(&key.hash().to_le_bytes()).unwrap();
+        for value in &[u32::MAX, 2, 4] {
+            file.write_all(&value.to_le_bytes()).unwrap();
+        }
+        drop(file);
+
+        assert!(load(&path, &key).is_none());
+    }
+
+    #[test]
+    fn only_the_newest_atlases_are_kept() {
+        let dir = TempDir::new("tracii").unwrap();
+        for height in 1..6 {
+            let key = key((1, height));
+            save(&atlas_path(dir.path(), &key), &key, &masks((1, height)));
+        }
+        File::create(dir.path().join("notes.txt")).unwrap();
+
+        prune(dir.path(), 2);
+        let names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&String::from("notes.txt")));
+    }
+}
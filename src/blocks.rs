@@ -0,0 +1,330 @@
+use convert::{AsciiArt, Cell, Layout};
+use image::{ImageBuffer, Rgb, RgbImage};
+use palette::Palette;
+use render_glyphs::GlyphMask;
+
+// Upper and lower half, each cell shows two pixels stacked
+pub static HALF_BLOCKS: &str = "▀▄";
+
+// Every pattern of a 2x2 grid but the empty one, which a full block in the background color covers.
+// Flat cells fit every block equally well, and the first one wins.
+pub static QUADRANT_BLOCKS: &str = "█▀▄▌▐▖▗▘▙▚▛▜▝▞▟";
+
+// A block char and how much of each of its sub-pixels the font's glyph actually covers
+struct Block {
+    c: char,
+    weights: Vec<f32>,
+}
+
+// The mean coverage of the mask over each cell of a columns x rows grid
+fn sub_pixel_coverage(mask: &GlyphMask, columns: u32, rows: u32) -> Vec<f32> {
+    let mut weights = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let (left, right) = (column * mask.width / columns, (column + 1) * mask.width / columns);
+            let (top, bottom) = (row * mask.height / rows, (row + 1) * mask.height / rows);
+
+            let mut sum = 0.0;
+            let mut count = 0;
+            for y in top..bottom {
+                for x in left..right {
+                    sum += mask.coverage[(x + y * mask.width) as usize];
+                    count += 1;
+                }
+            }
+            weights.push(if count == 0 { 0.0 } else { sum / count as f32 });
+        }
+    }
+    weights
+}
+
+// The foreground and background that best reproduce the pixels through the weights,
+// solving the least squares fit of weight * fg + (1 - weight) * bg for each channel
+fn fit_colors(weights: &[f32], pixels: &[[u8; 3]]) -> ([f32; 3], [f32; 3]) {
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    for &weight in weights {
+        aa += weight * weight;
+        ab += weight * (1.0 - weight);
+        bb += (1.0 - weight) * (1.0 - weight);
+    }
+    let determinant = aa * bb - ab * ab;
+
+    let mut foreground = [0.0; 3];
+    let mut background = [0.0; 3];
+    for channel in 0..3 {
+        let (mut ap, mut bp, mut sum) = (0.0, 0.0, 0.0);
+        for (&weight, pixel) in weights.iter().zip(pixels) {
+            let value = pixel[channel] as f32;
+            ap += weight * value;
+            bp += (1.0 - weight) * value;
+            sum += value;
+        }
+
+        // Uniform weights, like a full block's, can only show one color
+        if determinant.abs() < 1e-6 {
+            foreground[channel] = sum / pixels.len() as f32;
+            background[channel] = foreground[channel];
+        } else {
+            foreground[channel] = ((bb * ap - ab * bp) / determinant).clamp(0.0, 255.0);
+            background[channel] = ((aa * bp - ab * ap) / determinant).clamp(0.0, 255.0);
+        }
+    }
+    (foreground, background)
+}
+
+// The mean color of the input over each area of a width x height grid. A filtered resize
+// would blend each sub-pixel with its neighbours and soften the very edges the blocks draw.
+fn area_means(input: &RgbImage, width: u32, height: u32) -> RgbImage {
+    ImageBuffer::from_fn(width, height, |x, y| {
+        // Areas smaller than a pixel take the one they start in
+        let (left, top) = (x * input.width() / width, y * input.height() / height);
+        let right = ((x + 1) * input.width() / width).max(left + 1);
+        let bottom = ((y + 1) * input.height() / height).max(top + 1);
+
+        let mut sum = [0u32; 3];
+        for source_y in top..bottom {
+            for source_x in left..right {
+                let pixel = input.get_pixel(source_x, source_y).data;
+                for channel in 0..3 {
+                    sum[channel] += pixel[channel] as u32;
+                }
+            }
+        }
+        let count = (right - left) * (bottom - top);
+        let mut data = [0u8; 3];
+        for channel in 0..3 {
+            data[channel] = ((sum[channel] + count / 2) / count) as u8;
+        }
+        Rgb { data }
+    })
+}
+
+fn quantize(color: [f32; 3]) -> [u8; 3] {
+    [color[0].round() as u8, color[1].round() as u8, color[2].round() as u8]
+}
+
+fn error(weights: &[f32], pixels: &[[u8; 3]], foreground: [u8; 3], background: [u8; 3]) -> f32 {
+    let mut sum = 0.0;
+    for (&weight, pixel) in weights.iter().zip(pixels) {
+        for channel in 0..3 {
+            let shown = weight * foreground[channel] as f32 + (1.0 - weight) * background[channel] as f32;
+            let delta = shown - pixel[channel] as f32;
+            sum += delta * delta;
+        }
+    }
+    sum
+}
+
+// Every cell is sampled as a sub_grid of (columns, rows) pixels, and drawn with whichever block
+// char and pair of palette colors reproduces them best. What a block covers is read from
+// the masks, so a font whose blocks do not fill the cell is still matched faithfully.
+pub fn convert(
+    input: &RgbImage,
+    masks: &[GlyphMask],
+    chars: &str,
+    sub_grid: (u32, u32),
+    layout: &Layout,
+    palette: &Palette
+) -> Option<AsciiArt> {
+    let (sub_columns, sub_rows) = sub_grid;
+    let blocks: Vec<Block> = chars
+        .chars()
+        .filter_map(|c| masks.iter().find(|mask| mask.c == c))
+        .map(|mask| Block {
+            c: mask.c,
+            weights: sub_pixel_coverage(mask, sub_columns, sub_rows),
+        })
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let (columns, rows) = (layout.columns, layout.rows);
+
+    // One pixel of the input's means per sub-pixel
+    let sub_pixels = area_means(input, columns * sub_columns, rows * sub_rows);
+
+    let mut cells = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut pixels = Vec::new();
+            for sub_row in 0..sub_rows {
+                for sub_column in 0..sub_columns {
+                    pixels.push(sub_pixels
+                        .get_pixel(column * sub_columns + sub_column, row * sub_rows + sub_row)
+                        .data);
+                }
+            }
+
+            let mut best: Option<(f32, Cell)> = None;
+            for block in &blocks {
+                let (foreground, background) = fit_colors(&block.weights, &pixels);
+                let foreground = palette.snap(quantize(foreground));
+                let background = palette.snap(quantize(background));
+                let score = error(&block.weights, &pixels, foreground, background);
+
+                if best.as_ref().is_none_or(|&(best_score, _)| score < best_score) {
+                    best = Some((score, Cell {
+                        c: block.c,
+                        foreground,
+                        background,
+                        render: None,
+                    }));
+                }
+            }
+
+            if let Some((_, cell)) = best {
+                cells.push(cell);
+            }
+        }
+    }
+
+    Some(AsciiArt {
+        columns,
+        rows,
+        cells,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static RED: [u8; 3] = [255, 0, 0];
+    static BLUE: [u8; 3] = [0, 0, 255];
+    static BLACK: [u8; 3] = [0, 0, 0];
+    static WHITE: [u8; 3] = [255, 255, 255];
+
+    // Each quadrant block with the upper left, upper right, lower left and lower right it fills
+    static QUADRANTS: [(char, [bool; 4]); 15] = [
+        ('█', [true, true, true, true]),
+        ('▀', [true, true, false, false]),
+        ('▄', [false, false, true, true]),
+        ('▌', [true, false, true, false]),
+        ('▐', [false, true, false, true]),
+        ('▖', [false, false, true, false]),
+        ('▗', [false, false, false, true]),
+        ('▘', [true, false, false, false]),
+        ('▙', [true, false, true, true]),
+        ('▚', [true, false, false, true]),
+        ('▛', [true, true, true, false]),
+        ('▜', [true, true, false, true]),
+        ('▝', [false, true, false, false]),
+        ('▞', [false, true, true, false]),
+        ('▟', [false, true, true, true]),
+    ];
+
+    // Masks of blocks that fill their quadrants exactly, as a font's ideally would
+    fn masks() -> Vec<GlyphMask> {
+        QUADRANTS
+            .iter()
+            .map(|&(c, filled)| {
+                let coverage = (0..8 * 16)
+                    .map(|i| {
+                        let quadrant = (i % 8 / 4) + 2 * (i / 8 / 8);
+                        if filled[quadrant] { 1.0 } else { 0.0 }
+                    })
+                    .collect();
+                GlyphMask { coverage, width: 8, height: 16, c, source: 0 }
+            })
+            .collect()
+    }
+
+    // One cell's worth of input, each quadrant a flat color
+    fn tile(quadrants: [[u8; 3]; 4]) -> RgbImage {
+        ImageBuffer::from_fn(8, 8, |x, y| Rgb { data: quadrants[(x / 4 + 2 * (y / 4)) as usize] })
+    }
+
+    fn cell(input: &RgbImage, chars: &str, sub_grid: (u32, u32)) -> Cell {
+        let layout = Layout { columns: 1, rows: 1, tile_width: 8, tile_height: 8 };
+        let mut art = convert(input, &masks(), chars, sub_grid, &layout, &Palette::default()).unwrap();
+        assert_eq!(art.cells.len(), 1);
+        art.cells.remove(0)
+    }
+
+    #[test]
+    fn coverage_is_averaged_over_each_sub_pixel() {
+        let masks = masks();
+        let upper = masks.iter().find(|mask| mask.c == '▀').unwrap();
+        assert_eq!(sub_pixel_coverage(upper, 1, 2), vec![1.0, 0.0]);
+        assert_eq!(sub_pixel_coverage(upper, 2, 2), vec![1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(sub_pixel_coverage(upper, 1, 1), vec![0.5]);
+
+        // A grid finer than the mask leaves the sub-pixels that get none of its pixels empty
+        let tiny = GlyphMask { coverage: vec![1.0], width: 1, height: 1, c: '█', source: 0 };
+        assert_eq!(sub_pixel_coverage(&tiny, 2, 1), vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn colors_fit_through_the_weights() {
+        let (foreground, background) = fit_colors(&[1.0, 0.0], &[RED, BLUE]);
+        assert_eq!((quantize(foreground), quantize(background)), (RED, BLUE));
+
+        // Half coverage over two pixels splits the difference both ways
+        let (foreground, background) = fit_colors(&[1.0, 0.5, 0.0], &[WHITE, [128, 128, 128], BLACK]);
+        assert_eq!((quantize(foreground), quantize(background)), (WHITE, BLACK));
+    }
+
+    #[test]
+    fn uniform_weights_fit_a_single_color() {
+        // The determinant is zero, so both colors are the mean
+        let (foreground, background) = fit_colors(&[1.0, 1.0], &[RED, BLUE]);
+        assert_eq!(foreground, background);
+        assert_eq!(quantize(foreground), [128, 0, 128]);
+    }
+
+    #[test]
+    fn sub_pixels_do_not_bleed_into_each_other() {
+        let means = area_means(&tile([RED, RED, BLUE, BLUE]), 1, 2);
+        assert_eq!((means.get_pixel(0, 0).data, means.get_pixel(0, 1).data), (RED, BLUE));
+
+        let means = area_means(&tile([RED, BLUE, BLUE, RED]), 1, 1);
+        assert_eq!(means.get_pixel(0, 0).data, [128, 0, 128]);
+
+        // A grid finer than the input repeats its pixels
+        let means = area_means(&tile([RED, BLUE, BLUE, RED]), 16, 16);
+        assert_eq!((means.get_pixel(7, 7).data, means.get_pixel(8, 7).data), (RED, BLUE));
+    }
+
+    #[test]
+    fn two_tone_tiles_pick_a_half_block() {
+        let cell = cell(&tile([RED, RED, BLUE, BLUE]), HALF_BLOCKS, (1, 2));
+        match cell.c {
+            '▀' => assert_eq!((cell.foreground, cell.background), (RED, BLUE)),
+            '▄' => assert_eq!((cell.foreground, cell.background), (BLUE, RED)),
+            c => panic!("{}", c),
+        }
+        assert!(cell.render.is_none());
+    }
+
+    #[test]
+    fn flat_tiles_are_a_single_color() {
+        for &(chars, sub_grid) in &[(HALF_BLOCKS, (1, 2)), (QUADRANT_BLOCKS, (2, 2))] {
+            let cell = cell(&tile([BLUE; 4]), chars, sub_grid);
+            assert_eq!((cell.foreground, cell.background), (BLUE, BLUE));
+        }
+    }
+
+    #[test]
+    fn diagonal_tiles_pick_a_diagonal_quadrant() {
+        let falling = cell(&tile([WHITE, BLACK, BLACK, WHITE]), QUADRANT_BLOCKS, (2, 2));
+        match falling.c {
+            '▚' => assert_eq!((falling.foreground, falling.background), (WHITE, BLACK)),
+            '▞' => assert_eq!((falling.foreground, falling.background), (BLACK, WHITE)),
+            c => panic!("{}", c),
+        }
+
+        let rising = cell(&tile([BLACK, RED, RED, BLACK]), QUADRANT_BLOCKS, (2, 2));
+        match rising.c {
+            '▞' => assert_eq!((rising.foreground, rising.background), (RED, BLACK)),
+            '▚' => assert_eq!((rising.foreground, rising.background), (BLACK, RED)),
+            c => panic!("{}", c),
+        }
+    }
+
+    #[test]
+    fn chars_without_a_mask_are_none() {
+        let layout = Layout { columns: 1, rows: 1, tile_width: 8, tile_height: 8 };
+        assert!(convert(&tile([RED; 4]), &[], HALF_BLOCKS, (1, 2), &layout, &Palette::default()).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+use convert::{AsciiArt, Cell, Layout};
+use image::{imageops, FilterType, RgbImage};
+use image_util::luminance;
+use palette::Palette;
+
+// A braille cell is a 2 wide, 4 tall grid of dots
+static DOTS_WIDE: u32 = 2;
+static DOTS_TALL: u32 = 4;
+
+// The bit each dot sets in the offset from U+2800, indexed [row][column].
+// Dots 1-6 run down the left then the right column, 7 and 8 were added underneath.
+static DOT_BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+pub fn braille_char(dots: [[bool; 2]; 4]) -> char {
+    let mut offset = 0;
+    for (row, bits) in dots.iter().zip(DOT_BITS.iter()) {
+        for (&dot, &bit) in row.iter().zip(bits.iter()) {
+            if dot {
+                offset |= bit;
+            }
+        }
+    }
+    ::std::char::from_u32(0x2800 + offset).unwrap_or('\u{2800}')
+}
+
+fn mean_color(colors: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u32; 3];
+    for color in colors {
+        for channel in 0..3 {
+            sum[channel] += color[channel] as u32;
+        }
+    }
+    let count = colors.len().max(1) as u32;
+    [(sum[0] / count) as u8, (sum[1] / count) as u8, (sum[2] / count) as u8]
+}
+
+// Every cell is sampled as 2x4 sub-pixels. The ones brighter than the cell's mean are
+// raised dots in the foreground color, the rest are the background.
+pub fn convert(
+    input: &RgbImage,
+    layout: &Layout,
+    palette: &Palette
+) -> AsciiArt {
+    // The cells are laid out exactly as they are for glyph matching
+    let (columns, rows) = (layout.columns, layout.rows);
+
+    // One pixel of the resized input per dot
+    let dots = imageops::resize(input, columns * DOTS_WIDE, rows * DOTS_TALL, FilterType::Triangle);
+
+    let mut cells = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut samples = [[[0u8; 3]; 2]; 4];
+            for (dot_row, sample_row) in samples.iter_mut().enumerate() {
+                for (dot_column, sample) in sample_row.iter_mut().enumerate() {
+                    *sample = dots
+                        .get_pixel(column * DOTS_WIDE + dot_column as u32,
+                                   row * DOTS_TALL + dot_row as u32)
+                        .data;
+                }
+            }
+
+            let threshold = samples
+                .iter()
+                .flat_map(|sample_row| sample_row.iter())
+                .map(|&sample| luminance(sample))
+                .sum::<f32>() / (DOTS_WIDE * DOTS_TALL) as f32;
+
+            let mut raised = [[false; 2]; 4];
+            let mut lit = Vec::new();
+            let mut unlit = Vec::new();
+            for (sample_row, raised_row) in samples.iter().zip(raised.iter_mut()) {
+                for (&sample, dot) in sample_row.iter().zip(raised_row.iter_mut()) {
+                    *dot = luminance(sample) > threshold;
+                    if *dot {
+                        lit.push(sample);
+                    } else {
+                        unlit.push(sample);
+                    }
+                }
+            }
+
+            // A flat cell has no raised dots, its foreground is never seen
+            let background = mean_color(&unlit);
+            let foreground = if lit.is_empty() {
+                background
+            } else {
+                mean_color(&lit)
+            };
+
+            cells.push(Cell {
+                c: braille_char(raised),
+                foreground: palette.snap(foreground),
+                background: palette.snap(background),
+                render: None,
+            });
+        }
+    }
+
+    AsciiArt {
+        columns,
+        rows,
+        cells,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_dot(row: usize, column: usize) -> char {
+        let mut dots = [[false; 2]; 4];
+        dots[row][column] = true;
+        braille_char(dots)
+    }
+
+    #[test]
+    fn each_dot_sets_its_own_bit() {
+        // Dots 1 to 8, by the (row, column) they sit at
+        let dots = [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1), (3, 0), (3, 1)];
+        let chars: String = dots.iter().map(|&(row, column)| one_dot(row, column)).collect();
+        assert_eq!(chars, "⠁⠂⠄⠈⠐⠠⡀⢀");
+    }
+
+    #[test]
+    fn dots_combine() {
+        assert_eq!(braille_char([[false; 2]; 4]), '\u{2800}');
+        assert_eq!(braille_char([[true; 2]; 4]), '\u{28FF}');
+        assert_eq!(braille_char([[true, false], [false, true], [false, false], [true, false]]), '\u{2851}');
+    }
+}
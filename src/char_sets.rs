@@ -0,0 +1,139 @@
+use error::{Result, TraciiError};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+// custom:<string> and file:<path> are accepted alongside these
+pub static CHAR_SET_NAMES: &[&str] = &[
+    "ascii",
+    "ascii-printable-with-space",
+    "blocks",
+    "box-drawing",
+    "braille",
+    "latin1",
+];
+
+static BLOCKS: &str = "▀▄█▌▐░▒▓";
+
+fn range(first: u32, last: u32) -> Vec<char> {
+    (first..=last).filter_map(::std::char::from_u32).collect()
+}
+
+// The chars of a single named set
+pub fn char_set(spec: &str) -> Result<Vec<char>> {
+    if let Some(custom) = spec.strip_prefix("custom:") {
+        return Ok(custom.chars().collect());
+    }
+
+    if let Some(path) = spec.strip_prefix("file:") {
+        let path = PathBuf::from(path);
+        let mut contents = String::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|error| TraciiError::Io { path: path.clone(), error })?;
+        // Line breaks only separate the chars in the file
+        return Ok(contents.chars().filter(|c| !c.is_control()).collect());
+    }
+
+    match spec {
+        "ascii" => Ok(range(33, 126)),
+        "ascii-printable-with-space" => Ok(range(32, 126)),
+        "blocks" => Ok(BLOCKS.chars().collect()),
+        "box-drawing" => Ok(range(0x2500, 0x257F)),
+        "braille" => Ok(range(0x2800, 0x28FF)),
+        // Everything printable past ASCII, without the no-break and soft hyphen spaces
+        "latin1" => Ok(range(0xA1, 0xFF).into_iter().filter(|&c| c != '\u{AD}').collect()),
+        _ => Err(TraciiError::InvalidArgument(format!(
+            "{} is not a char set, use one of {}, custom:<string> or file:<path>",
+            spec,
+            CHAR_SET_NAMES.join(", ")
+        ))),
+    }
+}
+
+// The union of several sets, each char once, in the order they first appear
+pub fn char_sets<S: AsRef<str>>(specs: &[S]) -> Result<Vec<char>> {
+    let mut chars = Vec::new();
+    for spec in specs {
+        for c in char_set(spec.as_ref())? {
+            if !chars.contains(&c) {
+                chars.push(c);
+            }
+        }
+    }
+    Ok(chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn named_sets_cover_their_ranges() {
+        let bounds = |name: &str| {
+            let chars = char_set(name).unwrap();
+            (chars.len(), chars[0], chars[chars.len() - 1])
+        };
+        assert_eq!(bounds("ascii"), (94, '!', '~'));
+        assert_eq!(bounds("ascii-printable-with-space"), (95, ' ', '~'));
+        assert_eq!(bounds("blocks"), (8, '▀', '▓'));
+        assert_eq!(bounds("box-drawing"), (128, '─', '╿'));
+        assert_eq!(bounds("braille"), (256, '\u{2800}', '\u{28FF}'));
+        assert_eq!(bounds("latin1"), (94, '¡', 'ÿ'));
+
+        let latin1 = char_set("latin1").unwrap();
+        assert!(!latin1.contains(&'\u{A0}'));
+        assert!(!latin1.contains(&'\u{AD}'));
+        assert!(latin1.contains(&'\u{AC}') && latin1.contains(&'\u{AE}'));
+
+        for name in CHAR_SET_NAMES {
+            assert!(char_set(name).unwrap().iter().all(|c| !c.is_control()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn custom_sets_are_taken_as_given() {
+        assert_eq!(char_set("custom:a b:c").unwrap(), vec!['a', ' ', 'b', ':', 'c']);
+        assert_eq!(char_set("custom:").unwrap(), Vec::<char>::new());
+    }
+
+    #[test]
+    fn file_sets_leave_out_control_chars() {
+        let dir = TempDir::new("tracii").unwrap();
+        let path = dir.path().join("chars.txt");
+        File::create(&path).unwrap().write_all("ab\r\n\tc░\n\u{7}d\n".as_bytes()).unwrap();
+
+        let chars = char_set(&format!("file:{}", path.display())).unwrap();
+        assert_eq!(chars, vec!['a', 'b', 'c', '░', 'd']);
+
+        match char_set(&format!("file:{}", dir.path().join("missing.txt").display())) {
+            Err(TraciiError::Io { path, .. }) => assert!(path.ends_with("missing.txt")),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn unions_keep_the_first_of_each_char() {
+        let chars = char_sets(&["custom:cab", "custom:bd", "custom:a"]).unwrap();
+        assert_eq!(chars, vec!['c', 'a', 'b', 'd']);
+
+        // ascii-printable-with-space is ascii and a space, which goes last
+        let chars = char_sets(&["ascii", "ascii-printable-with-space"]).unwrap();
+        assert_eq!(chars.len(), 95);
+        assert_eq!(chars[94], ' ');
+        assert_eq!(chars[..94], char_set("ascii").unwrap()[..]);
+    }
+
+    #[test]
+    fn unknown_names_are_an_error() {
+        match char_sets(&["ascii", "klingon"]) {
+            Err(TraciiError::InvalidArgument(message)) => {
+                assert!(message.starts_with("klingon is not a char set"));
+                assert!(CHAR_SET_NAMES.iter().all(|name| message.contains(name)));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use image::{self, imageops, FilterType, GenericImage, ImageBuffer, Rgb, RgbImage};
+use blocks;
+use error::{Result, TraciiError};
+use glyph_index::{self, GlyphIndex};
+use metrics::Metric;
+use render_glyphs::GlyphAtlas;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+// How many cells wide the output is when neither columns nor rows are asked for
+pub static DEFAULT_COLUMNS: u32 = 80;
+
+pub static SIZE_POLICY_NAMES: &[&str] = &["crop", "pad", "stretch"];
+
+// What to do when both the columns and rows are fixed and the input's shape disagrees with them.
+// Crop fills the grid and cuts off the excess, pad shows all of the input on a black border,
+// stretch fills the grid with all of it and gives up the aspect ratio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SizePolicy {
+    Crop,
+    Pad,
+    Stretch,
+}
+
+impl SizePolicy {
+    pub fn from_name(name: &str) -> Option<SizePolicy> {
+        match name {
+            "crop" => Some(SizePolicy::Crop),
+            "pad" => Some(SizePolicy::Pad),
+            "stretch" => Some(SizePolicy::Stretch),
+            _ => None,
+        }
+    }
+}
+
+// How the cells are laid over the input, each covering a tile_width x tile_height tile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+pub static MODE_NAMES: &[&str] = &["glyphs", "braille", "halfblock", "quadrant"];
+
+// Glyphs matches each cell against the atlas, the others compute their char from the cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Glyphs,
+    Braille,
+    HalfBlock,
+    Quadrant,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "glyphs" => Some(Mode::Glyphs),
+            "braille" => Some(Mode::Braille),
+            "halfblock" => Some(Mode::HalfBlock),
+            "quadrant" => Some(Mode::Quadrant),
+            _ => None,
+        }
+    }
+
+    // The chars a sub-cell mode draws with, glyph matching uses whatever it is given
+    pub fn chars(self) -> Option<Vec<char>> {
+        match self {
+            Mode::Glyphs => None,
+            Mode::Braille => Some((0x2800..0x2900).filter_map(::std::char::from_u32).collect()),
+            Mode::HalfBlock => Some(blocks::HALF_BLOCKS.chars().collect()),
+            Mode::Quadrant => Some(blocks::QUADRANT_BLOCKS.chars().collect()),
+        }
+    }
+}
+
+pub struct Cell {
+    pub c: char,
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+    // The atlas render the cell was matched to, sub-cell modes have none
+    pub render: Option<usize>,
+}
+
+pub struct AsciiArt {
+    pub columns: u32,
+    pub rows: u32,
+    pub cells: Vec<Cell>,
+}
+
+pub fn load_input(input_path: &Path) -> Result<RgbImage> {
+    image::open(input_path)
+        .map(|image| image.to_rgb())
+        .map_err(|error| TraciiError::ImageLoad { path: input_path.to_path_buf(), error })
+}
+
+// Lay cells with an h/w ratio of ratio over the input. With only one of columns or rows
+// the other follows from the input's aspect ratio, with both the policy decides.
+// A cell is never less than a pixel of the input, so more columns or rows than that are cut back.
+pub fn layout(
+    input: &RgbImage,
+    columns: Option<u32>,
+    rows: Option<u32>,
+    policy: SizePolicy,
+    ratio: f32
+) -> Layout {
+    let (width, height) = (input.width(), input.height());
+    let columns = columns.map(|columns| columns.min(width).max(1));
+    let rows = rows.map(|rows| rows.min(height).max(1));
+    let tile_height_for = |tile_width: u32| ((tile_width as f32 * ratio).round() as u32).max(1);
+
+    let (tile_width, tile_height) = match (columns, rows) {
+        (Some(columns), Some(rows)) => {
+            let across = width as f32 / columns as f32;
+            let down = height as f32 / (rows as f32 * ratio);
+            match policy {
+                SizePolicy::Crop => {
+                    let tile_width = (across.min(down) as u32).max(1);
+                    (tile_width, tile_height_for(tile_width))
+                }
+                SizePolicy::Pad => {
+                    let tile_width = (across.max(down).ceil() as u32).max(1);
+                    (tile_width, tile_height_for(tile_width))
+                }
+                SizePolicy::Stretch => ((width / columns).max(1), (height / rows).max(1)),
+            }
+        }
+        (None, Some(rows)) => {
+            let tile_height = (height / rows).max(1);
+            (((tile_height as f32 / ratio).round() as u32).max(1), tile_height)
+        }
+        (columns, None) => {
+            let tile_width = (width / columns.unwrap_or(DEFAULT_COLUMNS).min(width)).max(1);
+            (tile_width, tile_height_for(tile_width))
+        }
+    };
+
+    Layout {
+        columns: columns.unwrap_or((width / tile_width).max(1)),
+        rows: rows.unwrap_or((height / tile_height).max(1)),
+        tile_width,
+        tile_height,
+    }
+}
+
+// The input brought to exactly the pixels the layout covers. It is only resampled when it
+// has to be, otherwise it is centered on the grid, cropped at its edges or bordered in black.
+pub fn fit_input(input: &RgbImage, layout: &Layout, policy: SizePolicy) -> RgbImage {
+    let width = layout.columns * layout.tile_width;
+    let height = layout.rows * layout.tile_height;
+    if input.dimensions() == (width, height) {
+        return input.clone();
+    }
+
+    let scale_x = width as f32 / input.width() as f32;
+    let scale_y = height as f32 / input.height() as f32;
+    let scaled;
+    let source = match policy {
+        SizePolicy::Stretch => return imageops::resize(input, width, height, FilterType::Triangle),
+        SizePolicy::Crop if input.width() < width || input.height() < height => {
+            let scale = scale_x.max(scale_y);
+            scaled = imageops::resize(input,
+                                      (input.width() as f32 * scale).ceil() as u32,
+                                      (input.height() as f32 * scale).ceil() as u32,
+                                      FilterType::Triangle);
+            &scaled
+        }
+        SizePolicy::Pad if input.width() > width || input.height() > height => {
+            let scale = scale_x.min(scale_y);
+            scaled = imageops::resize(input,
+                                      ((input.width() as f32 * scale) as u32).max(1),
+                                      ((input.height() as f32 * scale) as u32).max(1),
+                                      FilterType::Triangle);
+            &scaled
+        }
+        _ => input,
+    };
+
+    let left = (width as i64 - source.width() as i64) / 2;
+    let top = (height as i64 - source.height() as i64) / 2;
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let source_x = x as i64 - left;
+        let source_y = y as i64 - top;
+        let inside = source_x >= 0 && source_x < source.width() as i64
+            && source_y >= 0 && source_y < source.height() as i64;
+        if inside {
+            *source.get_pixel(source_x as u32, source_y as u32)
+        } else {
+            Rgb { data: [0, 0, 0] }
+        }
+    })
+}
+
+pub fn convert(
+    input: &RgbImage,
+    atlas: &GlyphAtlas,
+    layout: &Layout,
+    metric: &dyn Metric,
+    index: Option<&GlyphIndex>
+) -> AsciiArt {
+    let Layout { columns, rows, tile_width, tile_height } = *layout;
+
+    // The masks are brought down to the tile size once, rather than blowing up every tile
+    let scaled_atlas = GlyphAtlas {
+        masks: atlas.masks
+            .iter()
+            .map(|mask| mask.resize(tile_width, tile_height))
+            .collect(),
+        color_pairs: atlas.color_pairs.clone(),
+    };
+    let everything: Vec<usize> = (0..atlas.len()).collect();
+
+    // Exhaustive matching colorizes every render for every tile into the one buffer, keeping
+    // them all would cost a tile's pixels for each mask in each color pair. The index only
+    // ever proposes a few, so those are kept the first time they are a candidate.
+    let mut scratch = RgbImage::new(tile_width, tile_height);
+    let mut scaled_renders: HashMap<usize, RgbImage> = HashMap::new();
+
+    let mut cells = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let x = column * tile_width;
+            let y = row * tile_height;
+            let tile = ImageBuffer::from_fn(tile_width, tile_height, |tx, ty| {
+                *input.get_pixel(x + tx, y + ty)
+            });
+
+            let mut best = 0;
+            let mut best_score = f32::MAX;
+            let nearest;
+            let candidates = match index {
+                Some(index) => {
+                    nearest = index.nearest(&glyph_index::features(&tile), glyph_index::CANDIDATES);
+                    &nearest
+                }
+                None => &everything,
+            };
+
+            for &candidate in candidates {
+                let render = match index {
+                    Some(_) => scaled_renders
+                        .entry(candidate)
+                        .or_insert_with(|| scaled_atlas.render(candidate).buffer),
+                    None => {
+                        let (mask, background, foreground) = scaled_atlas.parts(candidate);
+                        mask.colorize_into(background, foreground, &mut scratch);
+                        &scratch
+                    }
+                };
+
+                let score = metric.score(&tile, render);
+                if score < best_score {
+                    best_score = score;
+                    best = candidate;
+                }
+            }
+
+            let (mask, background, foreground) = atlas.parts(best);
+            cells.push(Cell {
+                c: mask.c,
+                foreground,
+                background,
+                render: Some(best),
+            });
+        }
+    }
+
+    AsciiArt {
+        columns,
+        rows,
+        cells,
+    }
+}
+
+impl AsciiArt {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for row in self.cells.chunks(self.columns as usize) {
+            for cell in row {
+                text.push(cell.c);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn preview(&self, atlas: &GlyphAtlas) -> Option<RgbImage> {
+        let offering = atlas.masks.first()?;
+        let cell_width = offering.width;
+        let cell_height = offering.height;
+
+        let mut result = ImageBuffer::from_pixel(
+            self.columns * cell_width,
+            self.rows * cell_height,
+            Rgb { data: [0, 0, 0] }
+        );
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            let column = index as u32 % self.columns;
+            let row = index as u32 / self.columns;
+
+            // Cells that were not matched are drawn with their char's mask, if the atlas has one
+            let render = match cell.render {
+                Some(render) => atlas.render(render),
+                None => match atlas.masks.iter().find(|mask| mask.c == cell.c) {
+                    Some(mask) => mask.colorize(cell.background, cell.foreground),
+                    None => continue,
+                },
+            };
+            result.copy_from(&render.buffer, column * cell_width, row * cell_height);
+        }
+
+        Some(result)
+    }
+
+    pub fn export(&self, work_dir: &Path, atlas: &GlyphAtlas) -> Result<()> {
+        let text_path = work_dir.join("ascii.txt");
+        File::create(&text_path)
+            .and_then(|mut file| file.write_all(self.to_text().as_bytes()))
+            .map_err(|error| TraciiError::Export { path: text_path, error })?;
+
+        if let Some(preview) = self.preview(atlas) {
+            let preview_path = work_dir.join("preview.png");
+            preview
+                .save(&preview_path)
+                .map_err(|error| TraciiError::ImageSave { path: preview_path, error })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics;
+    use render_glyphs::GlyphMask;
+
+    // Each pixel records where it came from, red the x and green the y, on a blue that
+    // tells it apart from the black border
+    fn coordinates(width: u32, height: u32) -> RgbImage {
+        ImageBuffer::from_fn(width, height, |x, y| Rgb { data: [x as u8, y as u8, 255] })
+    }
+
+    fn grid(layout: &Layout) -> (u32, u32, u32, u32) {
+        (layout.columns, layout.rows, layout.tile_width, layout.tile_height)
+    }
+
+    #[test]
+    fn one_dimension_follows_the_other() {
+        let input = coordinates(64, 48);
+        for &policy in &[SizePolicy::Crop, SizePolicy::Pad, SizePolicy::Stretch] {
+            assert_eq!(grid(&layout(&input, Some(16), None, policy, 2.0)), (16, 6, 4, 8));
+            assert_eq!(grid(&layout(&input, None, Some(6), policy, 2.0)), (16, 6, 4, 8));
+        }
+        // Without either it is DEFAULT_COLUMNS wide, cut back to the input's 64 pixels
+        assert_eq!(grid(&layout(&input, None, None, SizePolicy::Pad, 2.0)), (64, 24, 1, 2));
+    }
+
+    #[test]
+    fn cells_are_never_smaller_than_a_pixel() {
+        let input = coordinates(64, 48);
+        assert_eq!(grid(&layout(&input, Some(100_000), None, SizePolicy::Pad, 2.0)), (64, 24, 1, 2));
+        assert_eq!(grid(&layout(&input, None, Some(100_000), SizePolicy::Pad, 2.0)), (64, 48, 1, 1));
+
+        let layout = layout(&input, Some(100_000), Some(100_000), SizePolicy::Stretch, 2.0);
+        assert_eq!(grid(&layout), (64, 48, 1, 1));
+        assert_eq!(fit_input(&input, &layout, SizePolicy::Stretch).dimensions(), (64, 48));
+    }
+
+    #[test]
+    fn crop_cuts_the_excess_off_both_sides() {
+        let input = coordinates(64, 48);
+        let layout = layout(&input, Some(8), Some(8), SizePolicy::Crop, 2.0);
+        assert_eq!(grid(&layout), (8, 8, 3, 6));
+
+        // The 24 pixels across are the middle of the 64, starting at 20
+        let fitted = fit_input(&input, &layout, SizePolicy::Crop);
+        assert_eq!(fitted.dimensions(), (24, 48));
+        assert_eq!(fitted.get_pixel(0, 0).data, [20, 0, 255]);
+        assert_eq!(fitted.get_pixel(23, 47).data, [43, 47, 255]);
+    }
+
+    #[test]
+    fn crop_scales_up_an_input_smaller_than_the_grid() {
+        let input = coordinates(4, 4);
+        let layout = Layout { columns: 2, rows: 2, tile_width: 4, tile_height: 4 };
+        let fitted = fit_input(&input, &layout, SizePolicy::Crop);
+        assert_eq!(fitted.dimensions(), (8, 8));
+        assert!(fitted.pixels().all(|pixel| pixel.data[2] == 255));
+    }
+
+    #[test]
+    fn pad_borders_the_whole_input_in_black() {
+        let input = coordinates(64, 48);
+        let layout = layout(&input, Some(8), Some(8), SizePolicy::Pad, 2.0);
+        assert_eq!(grid(&layout), (8, 8, 8, 16));
+
+        // All 64 pixels across fit, and the 48 down sit in the middle of 128
+        let fitted = fit_input(&input, &layout, SizePolicy::Pad);
+        assert_eq!(fitted.dimensions(), (64, 128));
+        assert_eq!(fitted.get_pixel(0, 39).data, [0, 0, 0]);
+        assert_eq!(fitted.get_pixel(0, 40).data, [0, 0, 255]);
+        assert_eq!(fitted.get_pixel(63, 87).data, [63, 47, 255]);
+        assert_eq!(fitted.get_pixel(63, 88).data, [0, 0, 0]);
+    }
+
+    #[test]
+    fn stretch_fills_the_grid_with_all_of_the_input() {
+        let input = coordinates(64, 48);
+        let layout = layout(&input, Some(5), Some(5), SizePolicy::Stretch, 2.0);
+        assert_eq!(grid(&layout), (5, 5, 12, 9));
+
+        let fitted = fit_input(&input, &layout, SizePolicy::Stretch);
+        assert_eq!(fitted.dimensions(), (60, 45));
+        assert!(fitted.pixels().all(|pixel| pixel.data[2] == 255));
+    }
+
+    // Cells drawn straight from the atlas are matched back to the renders they came from,
+    // with or without the index narrowing the candidates
+    #[test]
+    fn cells_match_the_renders_they_were_drawn_with() {
+        let mask = |c, covered: fn(u32, u32) -> bool| GlyphMask {
+            coverage: (0..8 * 16).map(|i| if covered(i % 8, i / 8) { 1.0 } else { 0.0 }).collect(),
+            width: 8,
+            height: 16,
+            c,
+            source: 0,
+        };
+        let atlas = GlyphAtlas {
+            masks: vec![
+                mask('-', |_, y| y / 4 == 2),
+                mask('|', |x, _| x / 2 == 2),
+                mask('/', |x, y| x == 7 - y / 2),
+            ],
+            color_pairs: vec![([0, 0, 0], [255, 255, 255]), ([200, 0, 0], [0, 0, 200])],
+        };
+        let drawn = [4, 0, 2, 5, 1, 3];
+
+        let mut input = RgbImage::new(8 * drawn.len() as u32, 16);
+        for (column, &render) in drawn.iter().enumerate() {
+            input.copy_from(&atlas.render(render).buffer, 8 * column as u32, 0);
+        }
+        let layout = Layout { columns: drawn.len() as u32, rows: 1, tile_width: 8, tile_height: 16 };
+        let metric = metrics::metric_from_name("ssd").unwrap();
+        let glyph_index = GlyphIndex::new(&atlas);
+
+        for index in &[None, Some(&glyph_index)] {
+            let art = convert(&input, &atlas, &layout, metric.as_ref(), *index);
+            let matched: Vec<Option<usize>> = art.cells.iter().map(|cell| cell.render).collect();
+            let expected: Vec<Option<usize>> = drawn.iter().map(|&render| Some(render)).collect();
+            assert_eq!(matched, expected, "indexed: {}", index.is_some());
+        }
+    }
+
+    #[test]
+    fn an_input_the_size_of_the_grid_is_left_alone() {
+        let input = coordinates(64, 48);
+        let layout = layout(&input, Some(16), Some(6), SizePolicy::Pad, 2.0);
+        for &policy in &[SizePolicy::Crop, SizePolicy::Pad, SizePolicy::Stretch] {
+            assert_eq!(fit_input(&input, &layout, policy).into_raw(), input.clone().into_raw());
+        }
+    }
+}
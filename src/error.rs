@@ -0,0 +1,248 @@
+use image::ImageError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = ::std::result::Result<T, TraciiError>;
+
+#[derive(Debug)]
+pub enum TraciiError {
+    InvalidArgument(String),
+    PathNotFound(PathBuf),
+    WorkDir(io::Error),
+    FontNotFound(String),
+    AmbiguousFont { name: String, candidates: Vec<PathBuf> },
+    FontParse(PathBuf),
+    MultipleFontsInFile(PathBuf),
+    FaceNotFound { path: PathBuf, face: String },
+    NoAdvanceWidth(PathBuf),
+    PaletteParse(PathBuf),
+    NoGlyphs,
+    Io { path: PathBuf, error: io::Error },
+    ImageLoad { path: PathBuf, error: ImageError },
+    ImageSave { path: PathBuf, error: io::Error },
+    Export { path: PathBuf, error: io::Error },
+    PaneSizeMismatch { expected: (u32, u32), found: (u32, u32) },
+}
+
+impl TraciiError {
+    // 1 is a problem with the arguments, 2 with reading inputs, 3 with writing artifacts
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            TraciiError::InvalidArgument(_)
+            | TraciiError::PathNotFound(_)
+            | TraciiError::WorkDir(_)
+            | TraciiError::FontNotFound(_)
+            | TraciiError::AmbiguousFont { .. }
+            | TraciiError::FaceNotFound { .. } => 1,
+            TraciiError::FontParse(_)
+            | TraciiError::MultipleFontsInFile(_)
+            | TraciiError::NoAdvanceWidth(_)
+            | TraciiError::PaletteParse(_)
+            | TraciiError::NoGlyphs
+            | TraciiError::Io { .. }
+            | TraciiError::ImageLoad { .. } => 2,
+            TraciiError::ImageSave { .. }
+            | TraciiError::Export { .. }
+            | TraciiError::PaneSizeMismatch { .. } => 3,
+        }
+    }
+}
+
+impl fmt::Display for TraciiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TraciiError::InvalidArgument(ref message) => write!(f, "{}", message),
+            TraciiError::PathNotFound(ref path) => {
+                write!(f, "{} does not exist", path.to_string_lossy())
+            }
+            TraciiError::WorkDir(ref error) => {
+                write!(f, "There was an error making a temporary directory:\n{}", error)
+            }
+            TraciiError::FontNotFound(ref name) => {
+                write!(f, "Unable to locate a font with the name {}", name)
+            }
+            TraciiError::AmbiguousFont { ref name, ref candidates } => {
+                writeln!(f, "We found the the following font files that matched {}:\n", name)?;
+                for candidate in candidates {
+                    writeln!(f, "\t{}", candidate.to_string_lossy())?;
+                }
+                write!(f, "\nThere can only be one viable file, a --fontstyle or a full name narrows the search")
+            }
+            TraciiError::FontParse(ref path) => {
+                write!(f, "There were no fonts in {}", path.to_string_lossy())
+            }
+            TraciiError::MultipleFontsInFile(ref path) => {
+                write!(f,
+                       "There was more than one font in {0}, pick one with --fontindex or --fontface\n\
+                        tracii fonts faces {0} lists them",
+                       path.to_string_lossy())
+            }
+            TraciiError::FaceNotFound { ref path, ref face } => {
+                write!(f, "There is no face {} in {}", face, path.to_string_lossy())
+            }
+            TraciiError::NoAdvanceWidth(ref path) => {
+                write!(f,
+                       "The font in {} gives M no advance width, so there is no cell width to render at",
+                       path.to_string_lossy())
+            }
+            TraciiError::PaletteParse(ref path) => {
+                write!(f,
+                       "There were no system colors in {}, a palette file is an .itermcolors, \
+                        an Alacritty .yml or .toml, or Xresources",
+                       path.to_string_lossy())
+            }
+            TraciiError::NoGlyphs => write!(f, "There were no glyphs to render with"),
+            TraciiError::Io { ref path, ref error } => {
+                write!(f, "There was an error reading from {}\n{}", path.to_string_lossy(), error)
+            }
+            TraciiError::ImageLoad { ref path, ref error } => {
+                write!(f,
+                       "There was an error reading the input image {}:\n{}",
+                       path.to_string_lossy(),
+                       error)
+            }
+            TraciiError::ImageSave { ref path, ref error } => {
+                write!(f, "There was an error saving {}:\n{}", path.to_string_lossy(), error)
+            }
+            TraciiError::Export { ref path, ref error } => {
+                write!(f, "There was an error writing {}:\n{}", path.to_string_lossy(), error)
+            }
+            TraciiError::PaneSizeMismatch { expected, found } => {
+                write!(f,
+                       "The offering pane was {}x{}, found a pane that was {}x{}",
+                       expected.0,
+                       expected.1,
+                       found.0,
+                       found.1)
+            }
+        }
+    }
+}
+
+impl Error for TraciiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TraciiError::WorkDir(ref error)
+            | TraciiError::Io { ref error, .. }
+            | TraciiError::ImageSave { ref error, .. }
+            | TraciiError::Export { ref error, .. } => Some(error),
+            TraciiError::ImageLoad { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use convert::{load_input, AsciiArt};
+    use render_glyphs::{cell_metrics, load_glyphs, FontSource, GlyphAtlas};
+    use tempdir::TempDir;
+    use tests::{collection, fixture};
+    use web_export;
+    use {build_atlas, Options};
+
+    fn font(name: &str) -> FontSource {
+        FontSource {
+            path: fixture(name),
+            index: None,
+        }
+    }
+
+    fn empty_art() -> AsciiArt {
+        AsciiArt {
+            columns: 0,
+            rows: 0,
+            cells: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn missing_fonts_are_read_errors() {
+        let error = load_glyphs(&[font("missing.ttf")], &vec!['a']).err().unwrap();
+        assert!(matches!(error, TraciiError::Io { .. }), "{:?}", error);
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    #[test]
+    fn files_that_are_not_fonts_are_parse_errors() {
+        let error = cell_metrics(&font("disc.png")).err().unwrap();
+        assert!(matches!(error, TraciiError::FontParse(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    #[test]
+    fn missing_and_broken_images_are_load_errors() {
+        for name in &["missing.png", "DejaVuSansMono.ttf"] {
+            let error = load_input(&fixture(name)).err().unwrap();
+            assert!(matches!(error, TraciiError::ImageLoad { .. }), "{:?}", error);
+            assert_eq!(error.exit_code(), 2);
+        }
+    }
+
+    #[test]
+    fn unwritable_exports_are_export_errors() {
+        let missing_dir = fixture("missing");
+
+        let error = web_export::export(&missing_dir.join("ascii.html"), "").err().unwrap();
+        assert!(matches!(error, TraciiError::Export { .. }), "{:?}", error);
+        assert_eq!(error.exit_code(), 3);
+
+        let atlas = GlyphAtlas {
+            masks: Vec::new(),
+            color_pairs: Vec::new(),
+        };
+        let error = empty_art().export(&missing_dir, &atlas).err().unwrap();
+        assert!(matches!(error, TraciiError::Export { .. }), "{:?}", error);
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn collections_need_a_face_picked() {
+        let dir = TempDir::new("tracii").unwrap();
+        let path = dir.path().join("mono.ttc");
+        collection(&fixture("DejaVuSansMono.ttf"), 2, &path);
+        let mut source = FontSource { path: path.clone(), index: None };
+
+        let error = load_glyphs(&[source.clone()], &vec!['a']).err().unwrap();
+        match error {
+            TraciiError::MultipleFontsInFile(ref found) => assert_eq!(*found, path),
+            ref other => panic!("{:?}", other),
+        }
+        assert_eq!(error.exit_code(), 2);
+
+        source.index = Some(1);
+        assert!(load_glyphs(&[source], &vec!['a']).is_ok());
+    }
+
+    #[test]
+    fn faces_past_the_end_are_not_found() {
+        let dir = TempDir::new("tracii").unwrap();
+        let path = dir.path().join("mono.ttc");
+        collection(&fixture("DejaVuSansMono.ttf"), 2, &path);
+
+        for &(ref path, face) in &[(fixture("DejaVuSansMono.ttf"), 1), (path, 2)] {
+            let error = cell_metrics(&FontSource { path: path.clone(), index: Some(face) }).err().unwrap();
+            match error {
+                TraciiError::FaceNotFound { face: ref found, .. } => {
+                    assert_eq!(*found, format!("at index {}", face))
+                }
+                ref other => panic!("{:?}", other),
+            }
+            assert_eq!(error.exit_code(), 1);
+        }
+    }
+
+    #[test]
+    fn atlases_without_a_glyph_are_an_error() {
+        let mut options = Options::new(fixture("DejaVuSansMono.ttf"));
+        // Private use code points no font has
+        options.chars = vec!['\u{10FFFD}', '\u{10FFFC}'];
+
+        let error = build_atlas(&options).err().unwrap();
+        assert!(matches!(error, TraciiError::NoGlyphs), "{:?}", error);
+        assert_eq!(error.exit_code(), 2);
+    }
+}
@@ -0,0 +1,497 @@
+use error::{Result, TraciiError};
+use std::cmp::Reverse;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Font directories nest, but not this deep. It also keeps symlink loops finite.
+static MAX_DEPTH: usize = 8;
+
+static FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+
+pub static FONT_STYLE_NAMES: &[&str] = &["Regular", "Bold", "Italic", "BoldItalic"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    pub fn from_name(name: &str) -> Option<FontStyle> {
+        match name.to_lowercase().as_str() {
+            "regular" => Some(FontStyle::Regular),
+            "bold" => Some(FontStyle::Bold),
+            "italic" => Some(FontStyle::Italic),
+            "bolditalic" => Some(FontStyle::BoldItalic),
+            _ => None,
+        }
+    }
+
+    // Subfamily names are free text, "Book" and "Oblique" are as common as "Regular" and "Italic"
+    pub fn of_subfamily(subfamily: &str) -> FontStyle {
+        let subfamily = subfamily.to_lowercase();
+        let bold = subfamily.contains("bold");
+        let italic = subfamily.contains("italic") || subfamily.contains("oblique");
+        match (bold, italic) {
+            (false, false) => FontStyle::Regular,
+            (true, false) => FontStyle::Bold,
+            (false, true) => FontStyle::Italic,
+            (true, true) => FontStyle::BoldItalic,
+        }
+    }
+
+    fn canonical_subfamily(self) -> &'static str {
+        match self {
+            FontStyle::Regular => "regular",
+            FontStyle::Bold => "bold",
+            FontStyle::Italic => "italic",
+            FontStyle::BoldItalic => "bold italic",
+        }
+    }
+}
+
+// One face in a font file, as named by the file's own name table
+#[derive(Clone, Debug)]
+pub struct FontInfo {
+    pub path: PathBuf,
+    pub index: usize,
+    pub family: String,
+    pub style: String,
+    pub full_name: String,
+    pub monospace: bool,
+}
+
+// Every place fonts are installed on macOS, Linux and Windows
+pub fn font_directories() -> Vec<PathBuf> {
+    // https://support.apple.com/en-us/HT201722
+    let mut directories: Vec<PathBuf> = vec![
+        PathBuf::from("/Library/Fonts/"),
+        PathBuf::from("/Network/Library/Fonts/"),
+        PathBuf::from("/System/Library/Fonts/"),
+        PathBuf::from("/System Folder/Fonts/"),
+        PathBuf::from("/usr/share/fonts/"),
+        PathBuf::from("/usr/local/share/fonts/"),
+    ];
+
+    if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        directories.push(home.join("Library/Fonts/"));
+        directories.push(home.join(".local/share/fonts/"));
+        directories.push(home.join(".fonts/"));
+    }
+
+    if let Some(data_dirs) = env::var_os("XDG_DATA_DIRS") {
+        for data_dir in env::split_paths(&data_dirs) {
+            directories.push(data_dir.join("fonts"));
+        }
+    }
+
+    if let Some(windows) = env::var_os("WINDIR") {
+        directories.push(PathBuf::from(windows).join("Fonts"));
+    }
+
+    if let Some(local_app_data) = env::var_os("LOCALAPPDATA") {
+        directories.push(PathBuf::from(local_app_data).join("Microsoft\\Windows\\Fonts"));
+    }
+
+    // The XDG list usually repeats /usr/share
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for directory in directories {
+        let canonical = directory.canonicalize().unwrap_or(directory);
+        if !unique.contains(&canonical) {
+            unique.push(canonical);
+        }
+    }
+    unique
+}
+
+// Every face of every font file under the directories. Unreadable files are skipped.
+pub fn scan(directories: &[PathBuf]) -> Vec<FontInfo> {
+    let mut files = Vec::new();
+    for directory in directories {
+        collect_font_files(directory, 0, &mut files);
+    }
+    files.sort();
+
+    let mut fonts = Vec::new();
+    for file in files {
+        if let Ok(faces) = read_font_info(&file) {
+            fonts.extend(faces);
+        }
+    }
+    fonts
+}
+
+fn collect_font_files(directory: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_font_files(&path, depth + 1, files);
+        } else if is_font_file(&path) {
+            files.push(path);
+        }
+    }
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            FONT_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        })
+}
+
+pub fn read_font_info(path: &Path) -> Result<Vec<FontInfo>> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|error| TraciiError::Io { path: path.to_path_buf(), error })?;
+
+    let faces = face_offsets(&bytes).ok_or_else(|| TraciiError::FontParse(path.to_path_buf()))?;
+
+    let mut fonts = Vec::new();
+    for (index, offset) in faces.into_iter().enumerate() {
+        if let Some(names) = face_names(&bytes, offset) {
+            fonts.push(FontInfo {
+                path: path.to_path_buf(),
+                index,
+                family: names.family,
+                style: names.style,
+                full_name: names.full_name,
+                monospace: is_monospace(&bytes, offset),
+            });
+        }
+    }
+    Ok(fonts)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let slice = bytes.get(offset..offset + 2)?;
+    Some(u16::from(slice[0]) << 8 | u16::from(slice[1]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from(read_u16(bytes, offset)?) << 16 | u32::from(read_u16(bytes, offset + 2)?))
+}
+
+// Where each face's table directory starts. A collection lists them in its header.
+pub fn face_offsets(bytes: &[u8]) -> Option<Vec<usize>> {
+    if bytes.get(0..4)? == b"ttcf" {
+        let count = read_u32(bytes, 8)? as usize;
+        (0..count)
+            .map(|face| read_u32(bytes, 12 + face * 4).map(|offset| offset as usize))
+            .collect()
+    } else {
+        Some(vec![0])
+    }
+}
+
+// The (offset, length) of a table in the face whose directory starts at face_offset
+pub fn find_table(bytes: &[u8], face_offset: usize, tag: &[u8; 4]) -> Option<(usize, usize)> {
+    let table_count = read_u16(bytes, face_offset + 4)? as usize;
+    for table in 0..table_count {
+        let record = face_offset + 12 + table * 16;
+        if bytes.get(record..record + 4)? == tag {
+            let offset = read_u32(bytes, record + 8)? as usize;
+            let length = read_u32(bytes, record + 12)? as usize;
+            return Some((offset, length));
+        }
+    }
+    None
+}
+
+// The post table's isFixedPitch, or failing that a PANOSE proportion of monospaced
+fn is_monospace(bytes: &[u8], face_offset: usize) -> bool {
+    if let Some(fixed_pitch) = find_table(bytes, face_offset, b"post")
+        .and_then(|(table, _)| read_u32(bytes, table + 12)) {
+        if fixed_pitch != 0 {
+            return true;
+        }
+    }
+
+    find_table(bytes, face_offset, b"OS/2")
+        .and_then(|(table, _)| bytes.get(table + 35))
+        .is_some_and(|&proportion| proportion == 9)
+}
+
+struct FaceNames {
+    family: String,
+    style: String,
+    full_name: String,
+}
+
+// name IDs from the OpenType name table
+static FAMILY: u16 = 1;
+static SUBFAMILY: u16 = 2;
+static FULL_NAME: u16 = 4;
+static TYPOGRAPHIC_FAMILY: u16 = 16;
+static TYPOGRAPHIC_SUBFAMILY: u16 = 17;
+
+fn face_names(bytes: &[u8], face_offset: usize) -> Option<FaceNames> {
+    let (table, _) = find_table(bytes, face_offset, b"name")?;
+    let count = read_u16(bytes, table + 2)? as usize;
+    let storage = table + read_u16(bytes, table + 4)? as usize;
+
+    // The best string seen for each name ID, with how much we like its platform
+    let mut found: Vec<(u16, u8, String)> = Vec::new();
+    for record in 0..count {
+        let record = table + 6 + record * 12;
+        let platform = read_u16(bytes, record)?;
+        let encoding = read_u16(bytes, record + 2)?;
+        let language = read_u16(bytes, record + 4)?;
+        let name_id = read_u16(bytes, record + 6)?;
+        let length = read_u16(bytes, record + 8)? as usize;
+        let offset = storage + read_u16(bytes, record + 10)? as usize;
+        let raw = match bytes.get(offset..offset + length) {
+            Some(raw) => raw,
+            None => continue,
+        };
+
+        // English Windows names first, then Unicode, then Mac Roman
+        let (preference, text) = match (platform, encoding, language) {
+            (3, _, 0x409) => (3, decode_utf16(raw)),
+            (0, _, _) | (3, _, _) => (2, decode_utf16(raw)),
+            (1, 0, 0) => (1, raw.iter().map(|&byte| byte as char).collect()),
+            _ => continue,
+        };
+
+        match found.iter().position(|entry| entry.0 == name_id) {
+            Some(existing) => {
+                if found[existing].1 < preference {
+                    found[existing] = (name_id, preference, text);
+                }
+            }
+            None => found.push((name_id, preference, text)),
+        }
+    }
+
+    let name = |ids: &[u16]| {
+        ids.iter()
+            .filter_map(|id| found.iter().find(|entry| entry.0 == *id))
+            .map(|entry| entry.2.clone())
+            .next()
+    };
+
+    let family = name(&[TYPOGRAPHIC_FAMILY, FAMILY])?;
+    let style = name(&[TYPOGRAPHIC_SUBFAMILY, SUBFAMILY]).unwrap_or_else(|| String::from("Regular"));
+    let full_name = name(&[FULL_NAME]).unwrap_or_else(|| format!("{} {}", family, style));
+
+    Some(FaceNames {
+        family,
+        style,
+        full_name,
+    })
+}
+
+fn decode_utf16(raw: &[u8]) -> String {
+    let units: Vec<u16> = raw
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| u16::from(pair[0]) << 8 | u16::from(pair[1]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+// The index of the face in a collection whose full name matches
+pub fn face_index(path: &Path, face_name: &str) -> Result<usize> {
+    let wanted = face_name.to_lowercase();
+    read_font_info(path)?
+        .into_iter()
+        .find(|font| font.full_name.to_lowercase() == wanted)
+        .map(|font| font.index)
+        .ok_or_else(|| TraciiError::FaceNotFound {
+            path: path.to_path_buf(),
+            face: format!("named {}", face_name),
+        })
+}
+
+// How well a face fits a name and style. Full name matches beat family matches,
+// then the style's class, then its exact subfamily name.
+fn rank(font: &FontInfo, wanted: &str, style: FontStyle) -> Option<(bool, bool, bool)> {
+    let full_name = font.full_name.to_lowercase() == wanted;
+    if !full_name && font.family.to_lowercase() != wanted {
+        return None;
+    }
+    Some((full_name,
+          FontStyle::of_subfamily(&font.style) == style,
+          font.style.to_lowercase() == style.canonical_subfamily()))
+}
+
+// Find the font file, and the face within it, for a name. Without a style, a family
+// resolves to its regular face.
+pub fn find_font(
+    name: &str,
+    style: Option<FontStyle>,
+    directories: &[PathBuf]
+) -> Result<(PathBuf, usize)> {
+    let fonts = scan(directories);
+    let wanted = name.to_lowercase();
+
+    let mut ranked: Vec<((bool, bool, bool), &FontInfo)> = fonts
+        .iter()
+        .filter_map(|font| {
+            rank(font, &wanted, style.unwrap_or(FontStyle::Regular)).map(|rank| (rank, font))
+        })
+        .collect();
+    ranked.sort_by_key(|&(rank, _)| Reverse(rank));
+
+    // When a style was asked for, faces of any other style are never the answer
+    if style.is_some() {
+        ranked.retain(|&((_, style_matches, _), _)| style_matches);
+    }
+
+    let best = match ranked.first() {
+        Some(&(best, _)) => best,
+        None => return Err(TraciiError::FontNotFound(String::from(name))),
+    };
+
+    let mut faces: Vec<(PathBuf, usize)> = Vec::new();
+    for &(rank, font) in &ranked {
+        let face = (font.path.clone(), font.index);
+        if rank == best && !faces.contains(&face) {
+            faces.push(face);
+        }
+    }
+
+    if faces.len() == 1 {
+        return Ok(faces.remove(0));
+    }
+
+    // Every candidate is listed, best first, so the choice can be narrowed
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for &(_, font) in &ranked {
+        if !candidates.contains(&font.path) {
+            candidates.push(font.path.clone());
+        }
+    }
+    Err(TraciiError::AmbiguousFont {
+        name: String::from(name),
+        candidates,
+    })
+}
+
+// The monospace faces whose family or full name contains the pattern, sorted by family then style
+pub fn list_monospace(pattern: Option<&str>, directories: &[PathBuf]) -> Vec<FontInfo> {
+    let pattern = pattern.map(|pattern| pattern.to_lowercase());
+    let mut fonts: Vec<FontInfo> = scan(directories)
+        .into_iter()
+        .filter(|font| font.monospace)
+        .filter(|font| match pattern {
+            Some(ref pattern) => {
+                font.family.to_lowercase().contains(pattern.as_str())
+                    || font.full_name.to_lowercase().contains(pattern.as_str())
+            }
+            None => true,
+        })
+        .collect();
+    fonts.sort_by(|a, b| (&a.family, &a.style, &a.path).cmp(&(&b.family, &b.style, &b.path)));
+    fonts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::fixture;
+
+    // Fonts with little more than name tables, see tests/fixtures/fonts/README
+    fn fixture_fonts() -> Vec<PathBuf> {
+        vec![fixture("fonts")]
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn monospace_by_fixed_pitch_or_panose() {
+        let monospace = |name: &str| read_font_info(&fixture("fonts").join(name)).unwrap()[0].monospace;
+        assert!(monospace("FixtureMono-Regular.ttf"));
+        assert!(monospace("nested/FixtureMono-Oblique.otf"));
+        assert!(!monospace("FixtureSans.ttf"));
+    }
+
+    #[test]
+    fn collections_list_every_face() {
+        let faces = read_font_info(&fixture("fonts/FixtureCode.ttc")).unwrap();
+        let names: Vec<(usize, &str)> = faces.iter().map(|face| (face.index, face.full_name.as_str())).collect();
+        assert_eq!(names, vec![(0, "Fixture Code"), (1, "Fixture Code Bold")]);
+        assert_eq!(face_index(&fixture("fonts/FixtureCode.ttc"), "fixture code bold").unwrap(), 1);
+    }
+
+    #[test]
+    fn list_monospace_sorts_and_filters() {
+        let listed: Vec<(String, String)> = list_monospace(None, &fixture_fonts())
+            .into_iter()
+            .map(|font| (font.family, font.style))
+            .collect();
+        let expected = vec![
+            ("Fixture Code", "Bold"),
+            ("Fixture Code", "Regular"),
+            ("Fixture Mono", "Bold"),
+            ("Fixture Mono", "Oblique"),
+            ("Fixture Mono", "Regular"),
+            ("Fixture Twin", "Regular"),
+            ("Fixture Twin", "Regular"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(family, style)| (String::from(family), String::from(style)))
+            .collect();
+        assert_eq!(listed, expected);
+
+        let code = list_monospace(Some("CODE"), &fixture_fonts());
+        assert_eq!(code.len(), 2);
+        assert!(list_monospace(Some("sans"), &fixture_fonts()).is_empty());
+    }
+
+    #[test]
+    fn find_font_prefers_the_regular_face_of_a_family() {
+        let (path, index) = find_font("fixture mono", None, &fixture_fonts()).unwrap();
+        assert_eq!((file_name(&path).as_str(), index), ("FixtureMono-Regular.ttf", 0));
+    }
+
+    #[test]
+    fn find_font_ranks_by_style() {
+        let (path, _) = find_font("Fixture Mono", Some(FontStyle::Bold), &fixture_fonts()).unwrap();
+        assert_eq!(file_name(&path), "FixtureMono-Bold.ttf");
+
+        // Oblique is the italic class, though not its canonical name
+        let (path, _) = find_font("Fixture Mono", Some(FontStyle::Italic), &fixture_fonts()).unwrap();
+        assert_eq!(file_name(&path), "FixtureMono-Oblique.otf");
+
+        let (path, index) = find_font("Fixture Code", Some(FontStyle::Bold), &fixture_fonts()).unwrap();
+        assert_eq!((file_name(&path).as_str(), index), ("FixtureCode.ttc", 1));
+    }
+
+    #[test]
+    fn find_font_matches_full_names() {
+        let (path, _) = find_font("fixture mono bold", None, &fixture_fonts()).unwrap();
+        assert_eq!(file_name(&path), "FixtureMono-Bold.ttf");
+    }
+
+    #[test]
+    fn find_font_reports_missing_and_ambiguous_fonts() {
+        match find_font("Fixture Sans", Some(FontStyle::Bold), &fixture_fonts()) {
+            Err(TraciiError::FontNotFound(_)) => {}
+            other => panic!("{:?}", other),
+        }
+
+        match find_font("Fixture Twin", None, &fixture_fonts()) {
+            Err(TraciiError::AmbiguousFont { candidates, .. }) => assert_eq!(candidates.len(), 2),
+            other => panic!("{:?}", other),
+        }
+    }
+}
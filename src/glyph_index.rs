@@ -0,0 +1,312 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use image_util::luminance;
+use render_glyphs::GlyphAtlas;
+use std::cmp::Ordering;
+use std::time::Instant;
+
+// The luminance grid every image is reduced to, roughly the shape of a cell
+static GRID_WIDTH: u32 = 2;
+static GRID_HEIGHT: u32 = 4;
+
+// How many nearest renders the index hands back for the metric to decide between
+pub static CANDIDATES: usize = 8;
+
+// Reduce an image to a downsampled luminance grid followed by the mean of its
+// dark pixels and the mean of its light pixels. For a glyph render those are the
+// foreground and background colors, for a tile they are the best guess at them.
+pub fn features(image: &RgbImage) -> Vec<f32> {
+    let (width, height) = image.dimensions();
+    let cells = (GRID_WIDTH * GRID_HEIGHT) as usize;
+    let mut grid = vec![0f32; cells];
+    let mut counts = vec![0f32; cells];
+    let mut total_luminance = 0f32;
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let cell = (x * GRID_WIDTH / width + (y * GRID_HEIGHT / height) * GRID_WIDTH) as usize;
+        let value = luminance(pixel.data);
+        grid[cell] += value;
+        counts[cell] += 1.0;
+        total_luminance += value;
+    }
+
+    let mut vector: Vec<f32> = grid
+        .iter()
+        .zip(counts.iter())
+        .map(|(sum, count)| if *count > 0.0 { sum / count / 255.0 } else { 0.0 })
+        .collect();
+
+    // Split the pixels around the mean luminance
+    let mean_luminance = total_luminance / (width * height).max(1) as f32;
+    let mut dark = [0f32; 3];
+    let mut light = [0f32; 3];
+    let mut dark_count = 0f32;
+    let mut light_count = 0f32;
+    for pixel in image.pixels() {
+        let (sum, count) = if luminance(pixel.data) < mean_luminance {
+            (&mut dark, &mut dark_count)
+        } else {
+            (&mut light, &mut light_count)
+        };
+        for (total, value) in sum.iter_mut().zip(pixel.data.iter()) {
+            *total += *value as f32;
+        }
+        *count += 1.0;
+    }
+
+    // A flat image is all light, so its dark color is the same
+    if dark_count == 0.0 {
+        dark = light;
+        dark_count = light_count;
+    }
+
+    for value in dark.iter() {
+        vector.push(value / dark_count.max(1.0) / 255.0);
+    }
+    for value in light.iter() {
+        vector.push(value / light_count.max(1.0) / 255.0);
+    }
+
+    vector
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+// Keep the k best (distance, index) pairs sorted, nearest first
+fn offer(best: &mut Vec<(f32, usize)>, k: usize, candidate: (f32, usize)) {
+    if best.len() == k && candidate.0 >= best[k - 1].0 {
+        return;
+    }
+
+    let position = best
+        .iter()
+        .position(|entry| entry.0 > candidate.0)
+        .unwrap_or(best.len());
+    best.insert(position, candidate);
+    best.truncate(k);
+}
+
+struct Node {
+    point: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+// A k-d tree over the feature vectors of a set of glyph renders
+pub struct GlyphIndex {
+    points: Vec<Vec<f32>>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl GlyphIndex {
+    pub fn new(atlas: &GlyphAtlas) -> GlyphIndex {
+        // Features only need a few pixels per grid cell, so colorize small masks
+        let small_atlas = GlyphAtlas {
+            masks: atlas.masks
+                .iter()
+                .map(|mask| mask.resize(GRID_WIDTH * 4, GRID_HEIGHT * 4))
+                .collect(),
+            color_pairs: atlas.color_pairs.clone(),
+        };
+
+        let points = (0..small_atlas.len())
+            .map(|index| features(&small_atlas.render(index).buffer))
+            .collect();
+        GlyphIndex::from_points(points)
+    }
+
+    pub fn from_points(points: Vec<Vec<f32>>) -> GlyphIndex {
+        let mut index = GlyphIndex {
+            points,
+            nodes: Vec::new(),
+            root: None,
+        };
+
+        let mut order: Vec<usize> = (0..index.points.len()).collect();
+        index.root = index.build(&mut order);
+        index
+    }
+
+    fn build(&mut self, order: &mut [usize]) -> Option<usize> {
+        if order.is_empty() {
+            return None;
+        }
+
+        // Split on the axis with the widest spread, features are far from uniform
+        let dimensions = self.points[order[0]].len();
+        let mut axis = 0;
+        let mut widest = -1f32;
+        for candidate in 0..dimensions {
+            let mut low = f32::MAX;
+            let mut high = f32::MIN;
+            for &point in order.iter() {
+                low = low.min(self.points[point][candidate]);
+                high = high.max(self.points[point][candidate]);
+            }
+            if high - low > widest {
+                widest = high - low;
+                axis = candidate;
+            }
+        }
+
+        order.sort_by(|a, b| {
+            self.points[*a][axis]
+                .partial_cmp(&self.points[*b][axis])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let median = order.len() / 2;
+        let point = order[median];
+        let (left_order, rest) = order.split_at_mut(median);
+        let left = self.build(left_order);
+        let right = self.build(&mut rest[1..]);
+
+        self.nodes.push(Node {
+            point,
+            axis,
+            left,
+            right,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    // The indices of the k renders nearest to the query, nearest first
+    pub fn nearest(&self, query: &[f32], k: usize) -> Vec<usize> {
+        let mut best = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.search(self.root, query, k, &mut best);
+        }
+        best.iter().map(|entry| entry.1).collect()
+    }
+
+    fn search(&self, node: Option<usize>, query: &[f32], k: usize, best: &mut Vec<(f32, usize)>) {
+        let node = match node {
+            Some(node) => &self.nodes[node],
+            None => return,
+        };
+
+        let point = &self.points[node.point];
+        offer(best, k, (distance(query, point), node.point));
+
+        let delta = query[node.axis] - point[node.axis];
+        let (near, far) = if delta < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+
+        self.search(near, query, k, best);
+
+        // Only cross the splitting plane if something over there could still make the cut
+        if best.len() < k || delta * delta < best[best.len() - 1].0 {
+            self.search(far, query, k, best);
+        }
+    }
+
+    // The same answer as nearest, by checking every point
+    pub fn exhaustive_nearest(&self, query: &[f32], k: usize) -> Vec<usize> {
+        let mut best = Vec::with_capacity(k + 1);
+        if k > 0 {
+            for (index, point) in self.points.iter().enumerate() {
+                offer(&mut best, k, (distance(query, point), index));
+            }
+        }
+        best.iter().map(|entry| entry.1).collect()
+    }
+}
+
+// A fixed test image: a diagonal color gradient with a bright disc in the middle
+fn benchmark_image() -> RgbImage {
+    ImageBuffer::from_fn(320, 240, |x, y| {
+        let dx = x as f32 - 160.0;
+        let dy = y as f32 - 120.0;
+        if dx * dx + dy * dy < 80.0 * 80.0 {
+            Rgb { data: [250, 250, 240] }
+        } else {
+            Rgb { data: [(x * 255 / 320) as u8, (y * 255 / 240) as u8, 128] }
+        }
+    })
+}
+
+// Time exhaustive search against the k-d tree over every tile of the test image
+pub fn benchmark(atlas: &GlyphAtlas, columns: u32, ratio: f32) {
+    let image = benchmark_image();
+    let tile_width = (image.width() / columns).max(1);
+    let tile_height = ((tile_width as f32 * ratio).round() as u32).max(1);
+
+    let mut queries = Vec::new();
+    for row in 0..image.height() / tile_height {
+        for column in 0..image.width() / tile_width {
+            let x = column * tile_width;
+            let y = row * tile_height;
+            let tile = ImageBuffer::from_fn(tile_width, tile_height, |tx, ty| {
+                *image.get_pixel(x + tx, y + ty)
+            });
+            queries.push(features(&tile));
+        }
+    }
+
+    let build_start = Instant::now();
+    let index = GlyphIndex::new(atlas);
+    let build_time = build_start.elapsed();
+
+    let exhaustive_start = Instant::now();
+    let exhaustive: Vec<Vec<usize>> = queries
+        .iter()
+        .map(|query| index.exhaustive_nearest(query, 1))
+        .collect();
+    let exhaustive_time = exhaustive_start.elapsed();
+
+    let indexed_start = Instant::now();
+    let indexed: Vec<Vec<usize>> = queries
+        .iter()
+        .map(|query| index.nearest(query, 1))
+        .collect();
+    let indexed_time = indexed_start.elapsed();
+
+    // The palette repeats some colors, so ties are judged by distance rather than index
+    let agreements = queries
+        .iter()
+        .zip(exhaustive.iter().zip(indexed.iter()))
+        .filter(|&(query, (a, b))| {
+            distance(query, &index.points[a[0]]) == distance(query, &index.points[b[0]])
+        })
+        .count();
+
+    println!("Index benchmark: {} renders, {} tiles", index.points.len(), queries.len());
+    println!("\tbuild:      {:?}", build_time);
+    println!("\texhaustive: {:?}", exhaustive_time);
+    println!("\tindexed:    {:?}", indexed_time);
+    println!("\tagreement:  {}/{}", agreements, queries.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nan_features_do_not_panic() {
+        let points = vec![vec![0.0, 1.0], vec![f32::NAN, 0.5], vec![1.0, 0.0], vec![0.5, 0.5]];
+        let index = GlyphIndex::from_points(points);
+        assert_eq!(index.nearest(&[1.0, 0.0], 2).len(), 2);
+    }
+
+    #[test]
+    fn nearest_matches_exhaustive() {
+        let points: Vec<Vec<f32>> = (0..50)
+            .map(|i| vec![(i * 7 % 50) as f32, (i * 13 % 50) as f32, (i % 5) as f32])
+            .collect();
+        let index = GlyphIndex::from_points(points);
+        // Ties can come back in either order, so the answers are compared by distance
+        let distances = |query: &[f32], found: Vec<usize>| -> Vec<f32> {
+            found.iter().map(|&point| distance(query, &index.points[point])).collect()
+        };
+        for query in &[[0.0, 0.0, 0.0], [25.0, 10.0, 2.0], [49.0, 49.0, 4.0]] {
+            assert_eq!(distances(query, index.nearest(query, 3)),
+                       distances(query, index.exhaustive_nearest(query, 3)));
+        }
+    }
+}
@@ -1,73 +1,293 @@
+use error::{Result, TraciiError};
 use image::{GenericImage, ImageBuffer, Rgb, RgbImage};
-use rand::{thread_rng, Rng};
-
-pub fn pane_scramble (
-    buffers: &Vec<&RgbImage>,
-    background: [u8; 3],
-    width_count: usize,
-    height_count: usize,
-) -> Option<RgbImage>
-{
-    if buffers.len() == 0 {
-        return None;
-    }
-    
-    let mut rand = thread_rng();
-    let mut panes: Vec<&&RgbImage> = buffers.iter().take(width_count * height_count).collect();
-    for i in 0..panes.len() {
-        let j = rand.next_u32() as usize % (panes.len() - 1);
-        if i == j {
-            continue;
+use rand::Rng;
+use seeded_rng::SeededRng;
+
+pub static SHEET_LAYOUT_NAMES: &[&str] = &["ordered", "shuffled"];
+
+// Ordered takes the first panes in the order they were given, shuffled scrambles them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SheetLayout {
+    Ordered,
+    Shuffled,
+}
+
+impl SheetLayout {
+    pub fn from_name(name: &str) -> Option<SheetLayout> {
+        match name {
+            "ordered" => Some(SheetLayout::Ordered),
+            "shuffled" => Some(SheetLayout::Shuffled),
+            _ => None,
         }
+    }
+}
+
+// The perceived brightness of a color, from 0 to 255, by the Rec. 601 weights
+pub fn luminance(data: [u8; 3]) -> f32 {
+    0.299 * data[0] as f32 + 0.587 * data[1] as f32 + 0.114 * data[2] as f32
+}
+
+// What a pane shows, printed beneath it as its code point and a swatch of each color
+pub struct PaneLabel {
+    pub c: char,
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+}
+
+pub struct Pane<'a> {
+    pub image: &'a RgbImage,
+    pub label: Option<PaneLabel>,
+}
+
+// How the panes are laid out on a contact sheet
+pub struct SheetOptions {
+    pub columns: usize,
+    pub rows: usize,
+    // Pixels of gutter around and between the panes
+    pub padding: u32,
+    pub gutter: [u8; 3],
+    pub labels: bool,
+    pub layout: SheetLayout,
+}
 
-        let temp = panes[i];
-        panes[i] = panes[j];
-        panes[j] = temp;
+impl Default for SheetOptions {
+    fn default() -> SheetOptions {
+        SheetOptions {
+            columns: 10,
+            rows: 5,
+            padding: 0,
+            gutter: [0, 0, 0],
+            labels: false,
+            layout: SheetLayout::Shuffled,
+        }
     }
+}
 
-    let offering = buffers[0];
-    let width = width_count * offering.width() as usize;
-    let height = height_count * offering.height() as usize;
+// A label is a line of 3x5 chars with a pixel between them and around the line
+static LABEL_CHAR_WIDTH: u32 = 3;
+static LABEL_CHAR_HEIGHT: u32 = 5;
+static LABEL_LINE_HEIGHT: u32 = LABEL_CHAR_HEIGHT + 2;
 
-    let mut result = ImageBuffer::from_pixel(
-        width  as u32,
-        height as u32,
-        Rgb{ data: background}
-    );
-    
-    'outer_loop: for w in 0..width_count {
-        for h in 0..height_count {
-            let pane = match buffers.get(width + height * width_count as usize) {
-                Some(pane) => pane,
-                None => break 'outer_loop,
-            };
-
-            if pane.width() as usize != width {
-                println!(
-                    "ERROR: the offering pane had width of {}, found a pane with width of {}", 
-                    width, 
-                    pane.width()
-                );
-                return None;
-            }
+// Labels are drawn bigger on panes wide enough to fit them
+static LABEL_PANE_WIDTH: u32 = 40;
 
-            if pane.height() as usize != height {
-                println!(
-                    "ERROR: the offering pane had height of {}, found a pane with height of {}", 
-                    height, 
-                    pane.height()
-                );
-                return None;
+// The rows of the few chars a label needs, each row's bits read from the left
+fn label_char(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        _ => [0; 5],
+    }
+}
+
+// Fill a rectangle, cut off at the right edge so it never spills into the next pane
+fn fill(sheet: &mut RgbImage, x: u32, y: u32, size: (u32, u32), color: [u8; 3], right: u32) {
+    for fill_y in y..y + size.1 {
+        for fill_x in x..(x + size.0).min(right) {
+            sheet.put_pixel(fill_x, fill_y, Rgb { data: color });
+        }
+    }
+}
+
+fn draw_label(sheet: &mut RgbImage, label: &PaneLabel, x: u32, y: u32, width: u32, scale: u32, gutter: [u8; 3]) {
+    // The text is whichever of black or white stands out from the gutter
+    let ink = if luminance(gutter) > 127.0 { [0, 0, 0] } else { [255, 255, 255] };
+    let right = x + width;
+    let top = y + scale;
+
+    let mut pen = x + scale;
+    for c in format!("U+{:04X}", label.c as u32).chars() {
+        for (row, bits) in label_char(c).iter().enumerate() {
+            for column in 0..LABEL_CHAR_WIDTH {
+                if bits >> (LABEL_CHAR_WIDTH - 1 - column) & 1 == 1 {
+                    fill(sheet, pen + column * scale, top + row as u32 * scale, (scale, scale), ink, right);
+                }
             }
+        }
+        pen += (LABEL_CHAR_WIDTH + 1) * scale;
+    }
+
+    for &color in &[label.background, label.foreground] {
+        pen += scale;
+        let side = LABEL_CHAR_HEIGHT * scale;
+        fill(sheet, pen, top, (side, side), color, right);
+        pen += side;
+    }
+}
+
+// Which of count panes go on the sheet and in what order, so that only those need drawing.
+// Shuffling before the grid is filled lets any of them end up on it.
+pub fn sheet_order(count: usize, options: &SheetOptions, rng: &mut SeededRng) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..count).collect();
+    if options.layout == SheetLayout::Shuffled {
+        rng.shuffle(&mut order);
+    }
+    order.truncate(options.columns * options.rows);
+    order
+}
+
+// Lay the panes out in order on a grid options.columns wide, at most options.rows tall.
+// Every pane must be the size of the first, and a sheet with fewer panes than the grid holds
+// is only as big as the rows they fill.
+pub fn contact_sheet(panes: &[Pane], options: &SheetOptions) -> Result<Option<RgbImage>> {
+    if panes.is_empty() || options.columns == 0 || options.rows == 0 {
+        return Ok(None);
+    }
+    let panes = &panes[..panes.len().min(options.columns * options.rows)];
 
-            result.copy_from(
-                *pane,
-                (w * width) as u32,
-                (h * height) as u32,
-            );
+    let (pane_width, pane_height) = panes[0].image.dimensions();
+    for pane in panes {
+        if pane.image.dimensions() != (pane_width, pane_height) {
+            return Err(TraciiError::PaneSizeMismatch {
+                expected: (pane_width, pane_height),
+                found: pane.image.dimensions(),
+            });
         }
     }
 
-    Some(result)
+    let scale = (pane_width / LABEL_PANE_WIDTH).max(1);
+    let label_height = if options.labels { LABEL_LINE_HEIGHT * scale } else { 0 };
+    let columns = options.columns.min(panes.len());
+    let rows = panes.len().div_ceil(options.columns);
+
+    let cell_width = pane_width + options.padding;
+    let cell_height = pane_height + label_height + options.padding;
+    let mut sheet = ImageBuffer::from_pixel(
+        options.padding + columns as u32 * cell_width,
+        options.padding + rows as u32 * cell_height,
+        Rgb { data: options.gutter }
+    );
+
+    for (index, pane) in panes.iter().enumerate() {
+        let x = options.padding + (index % options.columns) as u32 * cell_width;
+        let y = options.padding + (index / options.columns) as u32 * cell_height;
+        sheet.copy_from(pane.image, x, y);
+
+        if let (true, Some(label)) = (options.labels, pane.label.as_ref()) {
+            draw_label(&mut sheet, label, x, y + pane_height, pane_width, scale, options.gutter);
+        }
+    }
+
+    Ok(Some(sheet))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pane(image: &RgbImage) -> Pane<'_> {
+        Pane {
+            image,
+            label: Some(PaneLabel {
+                c: 'A',
+                background: [255, 0, 0],
+                foreground: [0, 0, 255],
+            }),
+        }
+    }
+
+    fn options(columns: usize, rows: usize, padding: u32, labels: bool) -> SheetOptions {
+        SheetOptions {
+            columns,
+            rows,
+            padding,
+            gutter: [10, 20, 30],
+            labels,
+            layout: SheetLayout::Ordered,
+        }
+    }
+
+    #[test]
+    fn sheets_fit_the_padded_grid() {
+        let image = ImageBuffer::from_pixel(8, 16, Rgb { data: [200, 200, 200] });
+        let panes: Vec<Pane> = (0..6).map(|_| pane(&image)).collect();
+
+        let sheet = contact_sheet(&panes, &options(3, 2, 4, false)).unwrap().unwrap();
+        assert_eq!(sheet.dimensions(), (4 + 3 * (8 + 4), 4 + 2 * (16 + 4)));
+        assert_eq!(sheet.get_pixel(0, 0).data, [10, 20, 30]);
+        assert_eq!(sheet.get_pixel(4, 4).data, [200, 200, 200]);
+        assert_eq!(sheet.get_pixel(12, 4).data, [10, 20, 30]);
+    }
+
+    #[test]
+    fn labels_add_a_line_beneath_each_pane() {
+        let image = ImageBuffer::from_pixel(40, 80, Rgb { data: [200, 200, 200] });
+        let panes: Vec<Pane> = (0..2).map(|_| pane(&image)).collect();
+
+        let sheet = contact_sheet(&panes, &options(2, 1, 2, true)).unwrap().unwrap();
+        assert_eq!(sheet.dimensions(), (2 + 2 * (40 + 2), 2 + 80 + LABEL_LINE_HEIGHT + 2));
+
+        // The swatches follow the five chars of U+0041, the background's first
+        let swatch_x = 2 + 1 + 6 * (LABEL_CHAR_WIDTH + 1) + 1;
+        assert_eq!(sheet.get_pixel(swatch_x, 2 + 80 + 1).data, [255, 0, 0]);
+    }
+
+    #[test]
+    fn partial_last_rows_are_left_as_gutter() {
+        let image = ImageBuffer::from_pixel(4, 4, Rgb { data: [200, 200, 200] });
+        let panes: Vec<Pane> = (0..5).map(|_| pane(&image)).collect();
+
+        let sheet = contact_sheet(&panes, &options(3, 5, 0, false)).unwrap().unwrap();
+        // Only the rows the panes fill are drawn
+        assert_eq!(sheet.dimensions(), (12, 8));
+        assert_eq!(sheet.get_pixel(4, 4).data, [200, 200, 200]);
+        assert_eq!(sheet.get_pixel(8, 4).data, [10, 20, 30]);
+
+        // Fewer panes than a row holds make a narrower sheet
+        let sheet = contact_sheet(&panes[..2], &options(3, 5, 0, false)).unwrap().unwrap();
+        assert_eq!(sheet.dimensions(), (8, 4));
+    }
+
+    #[test]
+    fn panes_beyond_the_grid_are_dropped() {
+        let image = ImageBuffer::from_pixel(4, 4, Rgb { data: [200, 200, 200] });
+        let panes: Vec<Pane> = (0..10).map(|_| pane(&image)).collect();
+        let sheet = contact_sheet(&panes, &options(2, 2, 0, false)).unwrap().unwrap();
+        assert_eq!(sheet.dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn panes_of_another_size_are_an_error() {
+        let small = ImageBuffer::from_pixel(4, 4, Rgb { data: [0, 0, 0] });
+        let large = ImageBuffer::from_pixel(4, 5, Rgb { data: [0, 0, 0] });
+        match contact_sheet(&[pane(&small), pane(&large)], &options(2, 1, 0, false)) {
+            Err(TraciiError::PaneSizeMismatch { expected, found }) => {
+                assert_eq!((expected, found), ((4, 4), (4, 5)));
+            }
+            other => panic!("{:?}", other.map(|sheet| sheet.map(|sheet| sheet.dimensions()))),
+        }
+    }
+
+    #[test]
+    fn empty_sheets_are_none() {
+        assert!(contact_sheet(&[], &options(2, 2, 0, false)).unwrap().is_none());
+    }
+
+    #[test]
+    fn a_seed_always_shuffles_the_same_way() {
+        let mut shuffled = options(10, 5, 0, false);
+        shuffled.layout = SheetLayout::Shuffled;
+        let order = |seed| sheet_order(200, &shuffled, &mut SeededRng::new(seed));
+
+        assert_eq!(order(7), order(7));
+        assert_eq!(order(7).len(), 50);
+        assert_ne!(order(7), order(8));
+        assert_ne!(order(7), sheet_order(200, &options(10, 5, 0, false), &mut SeededRng::new(7)));
+    }
+}
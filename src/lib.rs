@@ -0,0 +1,358 @@
+extern crate image;
+extern crate libc;
+extern crate rand;
+extern crate rusttype;
+#[cfg(test)]
+extern crate tempdir;
+
+mod ansi;
+mod atlas_cache;
+mod blocks;
+mod braille;
+mod char_sets;
+mod convert;
+mod error;
+mod font_discovery;
+mod glyph_index;
+mod image_util;
+mod metrics;
+mod palette;
+mod render_glyphs;
+mod seeded_rng;
+mod terminal;
+mod web_export;
+
+// The modules are private, this is everything the library offers
+pub use ansi::{render as render_ansi, OutputFormat, OUTPUT_FORMAT_NAMES};
+pub use atlas_cache::default_cache_dir;
+pub use char_sets::char_sets;
+pub use convert::{load_input, AsciiArt, Cell, Layout, Mode, SizePolicy, DEFAULT_COLUMNS, MODE_NAMES, SIZE_POLICY_NAMES};
+pub use error::{Result, TraciiError};
+pub use font_discovery::{face_index, find_font, font_directories, list_monospace, read_font_info};
+pub use font_discovery::{FontInfo, FontStyle, FONT_STYLE_NAMES};
+pub use glyph_index::benchmark as benchmark_index;
+pub use image_util::{contact_sheet, sheet_order, Pane, PaneLabel, SheetLayout, SheetOptions, SHEET_LAYOUT_NAMES};
+pub use metrics::METRIC_NAMES;
+pub use palette::{palette_sweep, Palette, THEME_NAMES};
+pub use render_glyphs::{export_glyph_renders, load_glyphs, render_glyphs, FontSource, GlyphAtlas, GlyphMask, GlyphRender};
+pub use render_glyphs::{cell_metrics, uncovered_chars, CellMetrics};
+pub use render_glyphs::{Overflow, OVERFLOW_NAMES};
+pub use seeded_rng::SeededRng;
+pub use terminal::terminal_size;
+pub use web_export::{export as export_web, to_html, to_svg};
+
+use image::RgbImage;
+use std::path::PathBuf;
+
+// The (background, foreground) pair used when no palette is asked for
+pub static DEFAULT_COLOR_PAIR: ([u8; 3], [u8; 3]) = ([240, 40, 14], [9, 200, 220]);
+
+// Everything that decides how an image becomes ASCII art
+pub struct Options {
+    pub font_path: PathBuf,
+    // Which face of a collection to use, a file with one face needs none
+    pub font_index: Option<usize>,
+    // Tried in order for chars the primary font has no glyph for
+    pub fallback_fonts: Vec<FontSource>,
+    pub chars: Vec<char>,
+    // How tall cells are rendered when no input decides it
+    pub cell_height: u32,
+    // The h/w ratio of a cell, taken from the font's metrics unless this overrides it
+    pub cell_ratio: Option<f32>,
+    pub overflow: Overflow,
+    pub color_pairs: Vec<([u8; 3], [u8; 3])>,
+    // What the terminal's 256 colors look like, the sub-cell modes only pick colors from it
+    pub palette: Palette,
+    // Without either the output is DEFAULT_COLUMNS wide, with one the other follows the input
+    pub columns: Option<u32>,
+    pub rows: Option<u32>,
+    // How an input is fit to both columns and rows, when both are given
+    pub size_policy: SizePolicy,
+    pub mode: Mode,
+    pub metric: String,
+    pub indexed: bool,
+    pub cache_dir: Option<PathBuf>,
+    pub rebuild_atlas: bool,
+}
+
+impl Options {
+    pub fn new(font_path: PathBuf) -> Options {
+        Options {
+            font_path,
+            font_index: None,
+            fallback_fonts: Vec::new(),
+            chars: (33..127).map(From::from).collect(),
+            cell_height: 80,
+            cell_ratio: None,
+            overflow: Overflow::Clip,
+            color_pairs: vec![DEFAULT_COLOR_PAIR],
+            palette: Palette::default(),
+            columns: None,
+            rows: None,
+            size_policy: SizePolicy::Pad,
+            mode: Mode::Glyphs,
+            metric: String::from("ssd"),
+            indexed: false,
+            cache_dir: None,
+            rebuild_atlas: false,
+        }
+    }
+
+    // The primary font followed by the fallbacks
+    pub fn font_chain(&self) -> Vec<FontSource> {
+        let primary = FontSource {
+            path: self.font_path.clone(),
+            index: self.font_index,
+        };
+        Some(primary).into_iter().chain(self.fallback_fonts.iter().cloned()).collect()
+    }
+}
+
+// The cell ratio asked for, or else the primary font's own
+pub fn cell_ratio(options: &Options) -> Result<f32> {
+    match options.cell_ratio {
+        Some(ratio) => Ok(ratio),
+        None => Ok(render_glyphs::cell_metrics(&options.font_chain()[0])?.ratio()),
+    }
+}
+
+// How the cells of the output are laid over the image
+pub fn layout(image: &RgbImage, options: &Options) -> Result<Layout> {
+    Ok(convert::layout(image, options.columns, options.rows, options.size_policy, cell_ratio(options)?))
+}
+
+// Rasterize the glyph masks for the options at cell_height, or reuse a cached atlas of them
+pub fn build_atlas(options: &Options) -> Result<GlyphAtlas> {
+    let ratio = cell_ratio(options)?;
+    let width = (options.cell_height as f32 / ratio) as u32;
+    build_atlas_sized(options, (width, options.cell_height))
+}
+
+// Rasterize the glyph masks at the size each cell of the image covers, so that matching
+// compares like for like instead of resampling every render down to the tile.
+// The block modes read their coverage off the masks instead, and a tile smaller than
+// their sub-grid leaves some of its sub-pixels without a pixel to cover, so they keep cell_height.
+// Braille converts without any glyphs, so it gets an empty atlas.
+pub fn build_atlas_for(image: &RgbImage, options: &Options) -> Result<GlyphAtlas> {
+    match options.mode {
+        Mode::Glyphs => {}
+        Mode::Braille => {
+            return Ok(GlyphAtlas {
+                masks: Vec::new(),
+                color_pairs: options.color_pairs.clone(),
+            })
+        }
+        Mode::HalfBlock | Mode::Quadrant => return build_atlas(options),
+    }
+    let layout = layout(image, options)?;
+    build_atlas_sized(options, (layout.tile_width, layout.tile_height))
+}
+
+fn build_atlas_sized(options: &Options, cell_size: (u32, u32)) -> Result<GlyphAtlas> {
+    let fonts = options.font_chain();
+    let chars = options.mode.chars().unwrap_or_else(|| options.chars.clone());
+    let metrics = render_glyphs::cell_metrics(&fonts[0])?;
+    let cell_size = (cell_size.0.max(1), cell_size.1.max(1));
+    let cache = match options.cache_dir {
+        Some(ref cache_dir) => {
+            let key = atlas_cache::AtlasKey::new(
+                &fonts,
+                &chars,
+                cell_size,
+                options.overflow
+            )?;
+            let path = atlas_cache::atlas_path(cache_dir, &key);
+            Some((key, path))
+        }
+        None => None,
+    };
+
+    let cached = match cache {
+        Some((ref key, ref path)) if !options.rebuild_atlas => atlas_cache::load(path, key),
+        _ => None,
+    };
+
+    let masks = match cached {
+        Some(masks) => masks,
+        None => {
+            let glyph_pairs = load_glyphs(&fonts, &chars)?;
+            let masks = render_glyphs(&glyph_pairs, &metrics, cell_size, options.overflow)?;
+            if let Some((ref key, ref path)) = cache {
+                atlas_cache::save(path, key, &masks);
+            }
+            masks
+        }
+    };
+
+    // Braille dots are drawn from their bits, whether or not the font has them
+    let uncovered = render_glyphs::uncovered_chars(&chars, &masks);
+    if !uncovered.is_empty() && options.mode != Mode::Braille {
+        let uncovered: String = uncovered.iter().collect();
+        eprintln!("WARN: The atlas has no glyph for {}", uncovered);
+    }
+
+    // Colors are only composited onto the masks when a render is asked for
+    let atlas = GlyphAtlas {
+        masks,
+        color_pairs: options.color_pairs.clone(),
+    };
+
+    // Braille only uses the atlas to export glyphs, it converts without it
+    if atlas.is_empty() && options.mode != Mode::Braille {
+        return Err(TraciiError::NoGlyphs);
+    }
+
+    Ok(atlas)
+}
+
+// Convert with an atlas that was already built, so it can be reused between images
+pub fn convert_with_atlas(image: &RgbImage, atlas: &GlyphAtlas, options: &Options) -> Result<AsciiArt> {
+    let layout = layout(image, options)?;
+    // Only a grid fixed in both directions can disagree with the image's shape,
+    // otherwise all that is left over is the remainder of a tile at the edges
+    let policy = match (options.columns, options.rows) {
+        (Some(_), Some(_)) => options.size_policy,
+        _ => SizePolicy::Crop,
+    };
+    let image = &convert::fit_input(image, &layout, policy);
+
+    let palette = &options.palette;
+    let blocks = |chars, sub_grid| {
+        blocks::convert(image, &atlas.masks, chars, sub_grid, &layout, palette)
+            .ok_or(TraciiError::NoGlyphs)
+    };
+
+    match options.mode {
+        Mode::Glyphs => {}
+        Mode::Braille => return Ok(braille::convert(image, &layout, palette)),
+        Mode::HalfBlock => return blocks(blocks::HALF_BLOCKS, (1, 2)),
+        Mode::Quadrant => return blocks(blocks::QUADRANT_BLOCKS, (2, 2)),
+    }
+
+    let metric = metrics::metric_from_name(&options.metric).ok_or_else(|| {
+        TraciiError::InvalidArgument(format!("There is no metric named {}", options.metric))
+    })?;
+
+    let index = if options.indexed {
+        Some(glyph_index::GlyphIndex::new(atlas))
+    } else {
+        None
+    };
+
+    Ok(convert::convert(
+        image,
+        atlas,
+        &layout,
+        metric.as_ref(),
+        index.as_ref()
+    ))
+}
+
+pub fn convert(image: &RgbImage, options: &Options) -> Result<AsciiArt> {
+    let atlas = build_atlas_for(image, options)?;
+    convert_with_atlas(image, &atlas, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::path::Path;
+    use std::time::Instant;
+
+    // Checked in under tests/fixtures, DejaVu Sans Mono and a small gradient with a disc
+    pub fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+    }
+
+    // A collection of copies of a font, every face sharing the one table directory
+    pub fn collection(font: &Path, faces: usize, path: &Path) {
+        let mut bytes = Vec::new();
+        File::open(font).unwrap().read_to_end(&mut bytes).unwrap();
+        let header = 12 + 4 * faces;
+
+        // Table offsets count from the start of the file, which the header now comes before
+        let tables = (bytes[4] as usize) << 8 | bytes[5] as usize;
+        for table in 0..tables {
+            let record = 12 + table * 16 + 8;
+            let offset = (0..4).fold(0, |offset, i| offset << 8 | bytes[record + i] as usize) + header;
+            for i in 0..4 {
+                bytes[record + i] = (offset >> (24 - 8 * i)) as u8;
+            }
+        }
+
+        let mut file = File::create(path).unwrap();
+        file.write_all(b"ttcf\x00\x01\x00\x00").unwrap();
+        file.write_all(&[0, 0, 0, faces as u8]).unwrap();
+        for _ in 0..faces {
+            file.write_all(&[0, 0, 0, header as u8]).unwrap();
+        }
+        file.write_all(&bytes).unwrap();
+    }
+
+    #[test]
+    fn quadrants_survive_tiles_smaller_than_the_sub_grid() {
+        let image = load_input(&fixture("disc.png")).unwrap();
+        let mut options = Options::new(fixture("DejaVuSansMono.ttf"));
+        options.mode = Mode::Quadrant;
+        // One pixel of the image per column, a quadrant has half a pixel
+        options.columns = Some(image.width());
+
+        let atlas = build_atlas_for(&image, &options).unwrap();
+        assert!(atlas.masks.iter().all(|mask| mask.height == options.cell_height));
+
+        let art = convert_with_atlas(&image, &atlas, &options).unwrap();
+        let used: HashSet<char> = art.cells.iter().map(|cell| cell.c).collect();
+        assert!(used.len() > 3, "only {:?} were used", used);
+    }
+
+    #[test]
+    fn braille_converts_without_an_atlas() {
+        let image = load_input(&fixture("disc.png")).unwrap();
+        let mut options = Options::new(fixture("DejaVuSansMono.ttf"));
+        options.mode = Mode::Braille;
+        options.columns = Some(16);
+
+        let atlas = build_atlas_for(&image, &options).unwrap();
+        assert!(atlas.is_empty());
+
+        let art = convert_with_atlas(&image, &atlas, &options).unwrap();
+        assert_eq!(art.cells.len(), (art.columns * art.rows) as usize);
+        assert!(art.cells.iter().all(|cell| ('\u{2800}'..='\u{28FF}').contains(&cell.c)));
+    }
+
+    // Run with --nocapture to see the timings
+    #[test]
+    fn indexed_conversion_agrees_with_exhaustive() {
+        let image = load_input(&fixture("disc.png")).unwrap();
+        let mut options = Options::new(fixture("DejaVuSansMono.ttf"));
+        options.columns = Some(32);
+        options.color_pairs = palette_sweep(&Palette::default(), &[0, 1, 4, 7, 9, 11, 15], 3.0);
+        let atlas = build_atlas_for(&image, &options).unwrap();
+
+        let start = Instant::now();
+        let exhaustive = convert_with_atlas(&image, &atlas, &options).unwrap();
+        let exhaustive_time = start.elapsed();
+
+        options.indexed = true;
+        let start = Instant::now();
+        let indexed = convert_with_atlas(&image, &atlas, &options).unwrap();
+        let indexed_time = start.elapsed();
+
+        let agreements = exhaustive
+            .cells
+            .iter()
+            .zip(&indexed.cells)
+            .filter(|&(a, b)| a.render == b.render)
+            .count();
+        println!("{} renders, {} cells", atlas.len(), exhaustive.cells.len());
+        println!("\texhaustive: {:?}", exhaustive_time);
+        println!("\tindexed:    {:?}", indexed_time);
+        println!("\tagreement:  {}/{}", agreements, exhaustive.cells.len());
+
+        assert_eq!(indexed.cells.len(), exhaustive.cells.len());
+        assert!(agreements * 2 > exhaustive.cells.len());
+    }
+}
@@ -0,0 +1,224 @@
+use image::RgbImage;
+use image_util::luminance;
+
+// Every metric compares a tile of the input against a glyph render of the same size.
+// Lower scores are better matches; the scale is up to the metric.
+pub trait Metric {
+    fn score(&self, tile: &RgbImage, render: &RgbImage) -> f32;
+}
+
+pub static METRIC_NAMES: &[&str] = &["ssd", "ssim", "edge", "histogram"];
+
+pub fn metric_from_name(name: &str) -> Option<Box<dyn Metric>> {
+    match name {
+        "ssd" => Some(Box::new(SumOfSquares)),
+        "ssim" => Some(Box::new(Ssim)),
+        "edge" => Some(Box::new(EdgeDirection)),
+        "histogram" => Some(Box::new(LuminanceHistogram { bins: 16 })),
+        _ => None,
+    }
+}
+
+fn luminance_plane(image: &RgbImage) -> Vec<f32> {
+    image.pixels().map(|pixel| luminance(pixel.data)).collect()
+}
+
+// Per pixel RGB distance, the straightforward choice
+pub struct SumOfSquares;
+
+impl Metric for SumOfSquares {
+    fn score(&self, tile: &RgbImage, render: &RgbImage) -> f32 {
+        let mut sum = 0f32;
+        for (a, b) in tile.pixels().zip(render.pixels()) {
+            for channel in 0..3 {
+                let delta = a.data[channel] as f32 - b.data[channel] as f32;
+                sum += delta * delta;
+            }
+        }
+        sum
+    }
+}
+
+// Structural similarity over the whole tile, averaged across the color channels.
+// The score is 1 - SSIM so that identical tiles score 0.
+pub struct Ssim;
+
+impl Metric for Ssim {
+    fn score(&self, tile: &RgbImage, render: &RgbImage) -> f32 {
+        let c1 = (0.01f32 * 255.0).powi(2);
+        let c2 = (0.03f32 * 255.0).powi(2);
+        let count = tile.pixels().count().max(1) as f32;
+
+        let mut total = 0f32;
+        for channel in 0..3 {
+            let xs: Vec<f32> = tile.pixels().map(|p| p.data[channel] as f32).collect();
+            let ys: Vec<f32> = render.pixels().map(|p| p.data[channel] as f32).collect();
+
+            let mean_x = xs.iter().sum::<f32>() / count;
+            let mean_y = ys.iter().sum::<f32>() / count;
+
+            let mut variance_x = 0f32;
+            let mut variance_y = 0f32;
+            let mut covariance = 0f32;
+            for (x, y) in xs.iter().zip(ys.iter()) {
+                variance_x += (x - mean_x) * (x - mean_x);
+                variance_y += (y - mean_y) * (y - mean_y);
+                covariance += (x - mean_x) * (y - mean_y);
+            }
+            variance_x /= count;
+            variance_y /= count;
+            covariance /= count;
+
+            let ssim = ((2.0 * mean_x * mean_y + c1) * (2.0 * covariance + c2))
+                / ((mean_x * mean_x + mean_y * mean_y + c1) * (variance_x + variance_y + c2));
+            total += ssim;
+        }
+
+        1.0 - total / 3.0
+    }
+}
+
+// Compares the direction of the Sobel gradients, so strokes in a tile prefer glyphs
+// with strokes running the same way. Mean color keeps flat regions sensible.
+pub struct EdgeDirection;
+
+fn sobel(image: &RgbImage) -> Vec<(f32, f32)> {
+    let (width, height) = image.dimensions();
+    let plane = luminance_plane(image);
+    let at = |x: i64, y: i64| {
+        let x = x.max(0).min(width as i64 - 1) as usize;
+        let y = y.max(0).min(height as i64 - 1) as usize;
+        plane[x + y * width as usize]
+    };
+
+    let mut gradients = Vec::with_capacity(plane.len());
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            gradients.push((gx, gy));
+        }
+    }
+    gradients
+}
+
+fn mean_color(image: &RgbImage) -> [f32; 3] {
+    let count = image.pixels().count().max(1) as f32;
+    let mut sum = [0f32; 3];
+    for pixel in image.pixels() {
+        for (total, value) in sum.iter_mut().zip(pixel.data.iter()) {
+            *total += *value as f32;
+        }
+    }
+    [sum[0] / count, sum[1] / count, sum[2] / count]
+}
+
+impl Metric for EdgeDirection {
+    fn score(&self, tile: &RgbImage, render: &RgbImage) -> f32 {
+        let mut dot = 0f32;
+        let mut norm_tile = 0f32;
+        let mut norm_render = 0f32;
+        for (a, b) in sobel(tile).iter().zip(sobel(render).iter()) {
+            dot += a.0 * b.0 + a.1 * b.1;
+            norm_tile += a.0 * a.0 + a.1 * a.1;
+            norm_render += b.0 * b.0 + b.1 * b.1;
+        }
+
+        // A flat tile only matches a flat glyph
+        let edge_score = match (norm_tile > 0.0, norm_render > 0.0) {
+            (false, false) => 0.0,
+            (true, true) => 1.0 - dot / (norm_tile.sqrt() * norm_render.sqrt()),
+            _ => 1.0,
+        };
+
+        let tile_mean = mean_color(tile);
+        let render_mean = mean_color(render);
+        let color_distance = (0..3)
+            .map(|channel| (tile_mean[channel] - render_mean[channel]).powi(2))
+            .sum::<f32>()
+            .sqrt() / (255.0 * 3f32.sqrt());
+
+        edge_score + color_distance
+    }
+}
+
+// L1 distance between normalized luminance histograms, ignoring where the ink is
+pub struct LuminanceHistogram {
+    pub bins: usize,
+}
+
+impl LuminanceHistogram {
+    fn histogram(&self, image: &RgbImage) -> Vec<f32> {
+        let mut histogram = vec![0f32; self.bins];
+        let plane = luminance_plane(image);
+        // Each value is split between its two nearest bins, otherwise resampling
+        // noise pushes flat colors across bin edges
+        for value in &plane {
+            let position = ((value / 256.0) * self.bins as f32 - 0.5).max(0.0);
+            let lower = (position as usize).min(self.bins - 1);
+            let upper = (lower + 1).min(self.bins - 1);
+            let weight = (position - lower as f32).min(1.0);
+            histogram[lower] += 1.0 - weight;
+            histogram[upper] += weight;
+        }
+
+        let count = plane.len().max(1) as f32;
+        for bin in &mut histogram {
+            *bin /= count;
+        }
+        histogram
+    }
+}
+
+impl Metric for LuminanceHistogram {
+    fn score(&self, tile: &RgbImage, render: &RgbImage) -> f32 {
+        self.histogram(tile)
+            .iter()
+            .zip(self.histogram(render).iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    // An 8x8 black tile with a white bar across it
+    fn bar(vertical: bool, offset: u32, thickness: u32) -> RgbImage {
+        ImageBuffer::from_fn(8, 8, |x, y| {
+            let across = if vertical { x } else { y };
+            if across >= offset && across < offset + thickness {
+                Rgb { data: [255, 255, 255] }
+            } else {
+                Rgb { data: [0, 0, 0] }
+            }
+        })
+    }
+
+    #[test]
+    fn identical_images_score_zero() {
+        let tile = bar(true, 3, 2);
+        for name in METRIC_NAMES {
+            let metric = metric_from_name(name).unwrap();
+            let score = metric.score(&tile, &tile);
+            assert!(score.abs() < 1e-4, "{} scored {}", name, score);
+        }
+    }
+
+    #[test]
+    fn closer_renders_score_lower() {
+        let tile = bar(true, 3, 2);
+        // The same stroke a pixel over, and a heavier one running the other way
+        let closer = bar(true, 4, 2);
+        let farther = bar(false, 3, 3);
+        for name in METRIC_NAMES {
+            let metric = metric_from_name(name).unwrap();
+            let (near, far) = (metric.score(&tile, &closer), metric.score(&tile, &farther));
+            assert!(near < far, "{} scored {} for the closer render and {} for the farther", name, near, far);
+        }
+    }
+}
@@ -0,0 +1,532 @@
+use error::{Result, TraciiError};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+pub static THEME_NAMES: &[&str] = &["xterm", "solarized", "gruvbox", "tango", "windows", "vscode"];
+
+// The 16 system colors of each theme in ANSI order, black, red, green, yellow, blue, magenta,
+// cyan and white followed by their bright versions
+static XTERM: [u32; 16] = [
+    0x000000, 0x800000, 0x008000, 0x808000, 0x000080, 0x800080, 0x008080, 0xc0c0c0,
+    0x808080, 0xff0000, 0x00ff00, 0xffff00, 0x0000ff, 0xff00ff, 0x00ffff, 0xffffff,
+];
+
+// Solarized puts its base tones where the blacks, whites and some of the bright colors go
+static SOLARIZED: [u32; 16] = [
+    0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+    0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+];
+
+static GRUVBOX: [u32; 16] = [
+    0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984,
+    0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+];
+
+static TANGO: [u32; 16] = [
+    0x2e3436, 0xcc0000, 0x4e9a06, 0xc4a000, 0x3465a4, 0x75507b, 0x06989a, 0xd3d7cf,
+    0x555753, 0xef2929, 0x8ae234, 0xfce94f, 0x729fcf, 0xad7fa8, 0x34e2e2, 0xeeeeec,
+];
+
+// Campbell, the scheme Windows Console has shipped with since Windows 10
+static WINDOWS: [u32; 16] = [
+    0x0c0c0c, 0xc50f1f, 0x13a10e, 0xc19c00, 0x0037da, 0x881798, 0x3a96dd, 0xcccccc,
+    0x767676, 0xe74856, 0x16c60c, 0xf9f1a5, 0x3b78ff, 0xb4009e, 0x61d6d6, 0xf2f2f2,
+];
+
+// The integrated terminal of VS Code's default dark theme
+static VSCODE: [u32; 16] = [
+    0x000000, 0xcd3131, 0x0dbc79, 0xe5e510, 0x2472c8, 0xbc3fbc, 0x11a8cd, 0xe5e5e5,
+    0x666666, 0xf14c4c, 0x23d18b, 0xf5f543, 0x3b8eea, 0xd670d6, 0x29b8db, 0xe5e5e5,
+];
+
+// How Alacritty names the system colors, its normal ones are 0-7 and its bright ones 8-15
+static ANSI_NAMES: &[&str] = &["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+// What each of the 256 indices of an 8-bit color terminal shows
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::from_system(&XTERM)
+    }
+}
+
+fn rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
+
+impl Palette {
+    // The system colors are up to the terminal, the 6x6x6 cube and the grayscale ramp after
+    // them are the same everywhere
+    fn from_system(system: &[u32; 16]) -> Palette {
+        let mut colors: Vec<[u8; 3]> = system.iter().map(|&color| rgb(color)).collect();
+
+        // The cube's levels are 0 then 95 to 255 in steps of 40
+        let level = |step: u8| if step == 0 { 0 } else { 55 + 40 * step };
+        for red in 0..6 {
+            for green in 0..6 {
+                for blue in 0..6 {
+                    colors.push([level(red), level(green), level(blue)]);
+                }
+            }
+        }
+
+        // 24 grays from 8 to 238, leaving out the black and white the cube already has
+        for gray in 0..24 {
+            let value = 8 + 10 * gray;
+            colors.push([value, value, value]);
+        }
+
+        Palette { colors }
+    }
+
+    pub fn theme(name: &str) -> Option<Palette> {
+        let system = match name {
+            "xterm" => &XTERM,
+            "solarized" => &SOLARIZED,
+            "gruvbox" => &GRUVBOX,
+            "tango" => &TANGO,
+            "windows" => &WINDOWS,
+            "vscode" => &VSCODE,
+            _ => return None,
+        };
+        Some(Palette::from_system(system))
+    }
+
+    // The system colors from an iTerm .itermcolors, an Alacritty .yml or .toml, or otherwise
+    // an Xresources file. Any the file leaves out stay xterm's.
+    pub fn load(path: &Path) -> Result<Palette> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|error| TraciiError::Io { path: path.to_path_buf(), error })?;
+
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        let system = match extension.to_lowercase().as_str() {
+            "itermcolors" => itermcolors(&contents),
+            "yml" | "yaml" | "toml" => alacritty(&contents),
+            _ => xresources(&contents),
+        };
+        if system.is_empty() {
+            return Err(TraciiError::PaletteParse(path.to_path_buf()));
+        }
+
+        let mut palette = Palette::default();
+        for (index, color) in system {
+            palette.colors[index] = color;
+        }
+        Ok(palette)
+    }
+
+    pub fn color(&self, index: u8) -> [u8; 3] {
+        self.colors[index as usize]
+    }
+
+    // The index out of indices whose color is nearest to color
+    pub fn nearest<I>(&self, color: [u8; 3], indices: I) -> u8
+        where I: IntoIterator<Item = u8>
+    {
+        let mut best_index = 0;
+        let mut best_distance = u32::MAX;
+
+        for index in indices {
+            let candidate = self.color(index);
+            let distance: u32 = (0..3)
+                .map(|channel| {
+                    let delta = color[channel] as i32 - candidate[channel] as i32;
+                    (delta * delta) as u32
+                })
+                .sum();
+
+            if distance < best_distance {
+                best_distance = distance;
+                best_index = index;
+            }
+        }
+
+        best_index
+    }
+
+    // The color of the palette nearest to color, what a terminal can actually show of it
+    pub fn snap(&self, color: [u8; 3]) -> [u8; 3] {
+        self.color(self.nearest(color, 0..=255))
+    }
+}
+
+// A color as #rrggbb, 0xrrggbb or X11's rgb:r/g/b with 1 to 4 hex digits a channel, quoted or not
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+
+    if let Some(channels) = value.strip_prefix("rgb:") {
+        let mut color = [0; 3];
+        let mut parts = channels.split('/');
+        for channel in &mut color {
+            let part = parts.next()?;
+            if part.is_empty() || part.len() > 4 {
+                return None;
+            }
+            // Scaled from however many digits there are to a byte
+            let max = (1u32 << (4 * part.len())) - 1;
+            *channel = (u32::from_str_radix(part, 16).ok()? * 255 / max) as u8;
+        }
+        return Some(color);
+    }
+
+    let hex = value.strip_prefix('#').or_else(|| value.strip_prefix("0x"))?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(rgb)
+}
+
+// Each "Ansi N Color" key of the plist is followed by a dict of its components from 0 to 1
+fn itermcolors(contents: &str) -> Vec<(usize, [u8; 3])> {
+    let mut system = Vec::new();
+    let mut rest = contents;
+    while let Some(start) = rest.find("<key>Ansi ") {
+        rest = &rest[start + "<key>Ansi ".len()..];
+        let index = rest.split(' ').next().and_then(|index| index.parse::<usize>().ok());
+        let dict = match rest.find("</dict>") {
+            Some(end) => &rest[..end],
+            None => break,
+        };
+
+        let component = |name: &str| {
+            let key = format!("<key>{} Component</key>", name);
+            let after = &dict[dict.find(&key)? + key.len()..];
+            let value = &after[after.find("<real>")? + "<real>".len()..];
+            let value: f32 = value[..value.find("</real>")?].trim().parse().ok()?;
+            Some((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+        };
+
+        if let (Some(index), Some(red), Some(green), Some(blue)) =
+            (index, component("Red"), component("Green"), component("Blue")) {
+            if index < 16 {
+                system.push((index, [red, green, blue]));
+            }
+        }
+    }
+    system
+}
+
+// Lines like *color4: #268bd2, whatever class or instance comes before the colorN,
+// and the #define names such files often give their colors
+fn xresources(contents: &str) -> Vec<(usize, [u8; 3])> {
+    let mut defines = HashMap::new();
+    let mut system = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(define) = line.strip_prefix("#define") {
+            let mut parts = define.split_whitespace();
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                defines.insert(name.to_string(), value.to_string());
+            }
+            continue;
+        }
+        if line.starts_with('!') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once(':') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let index = key
+            .trim()
+            .rsplit(['.', '*'])
+            .next()
+            .and_then(|name| name.strip_prefix("color"))
+            .and_then(|index| index.parse::<usize>().ok());
+        let value = value.trim();
+        let value = defines.get(value).map(String::as_str).unwrap_or(value);
+
+        if let (Some(index), Some(color)) = (index, parse_color(value)) {
+            if index < 16 {
+                system.push((index, color));
+            }
+        }
+    }
+    system
+}
+
+// The colors.normal and colors.bright sections, as YAML keys or TOML tables
+fn alacritty(contents: &str) -> Vec<(usize, [u8; 3])> {
+    let mut offset = None;
+    let mut system = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            offset = match line.trim_matches(|c| c == '[' || c == ']').trim() {
+                "colors.normal" => Some(0),
+                "colors.bright" => Some(8),
+                _ => None,
+            };
+            continue;
+        }
+
+        let (key, value) = match line.split_once(':').or_else(|| line.split_once('=')) {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+
+        // A YAML key with nothing after it opens a section
+        if value.is_empty() {
+            offset = match key {
+                "normal" => Some(0),
+                "bright" => Some(8),
+                _ => None,
+            };
+            continue;
+        }
+
+        let position = ANSI_NAMES.iter().position(|&name| name == key);
+        if let (Some(offset), Some(position), Some(color)) = (offset, position, parse_color(value)) {
+            system.push((offset + position, color));
+        }
+    }
+    system
+}
+
+// The WCAG relative luminance of a color, from 0 for black to 1 for white
+fn relative_luminance(color: [u8; 3]) -> f32 {
+    let linear = |channel: u8| {
+        let value = channel as f32 / 255.0;
+        if value <= 0.03928 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.0722 * linear(color[2])
+}
+
+// The WCAG contrast ratio of two colors, from 1 for the same luminance to 21 for black on white
+pub fn contrast_ratio(first: [u8; 3], second: [u8; 3]) -> f32 {
+    let (first, second) = (relative_luminance(first), relative_luminance(second));
+    (first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+// Every (background, foreground) pair of the palette colors at indices whose contrast ratio is
+// at least min_contrast. Indices that share a color, like 0 and 16, only sweep it once, and a
+// color is never paired with itself since nothing drawn in it would show.
+pub fn palette_sweep(palette: &Palette, indices: &[u8], min_contrast: f32) -> Vec<([u8; 3], [u8; 3])> {
+    let mut colors: Vec<[u8; 3]> = Vec::new();
+    for &index in indices {
+        let color = palette.color(index);
+        if !colors.contains(&color) {
+            colors.push(color);
+        }
+    }
+
+    let mut color_pairs = Vec::new();
+    for &background in &colors {
+        for &foreground in &colors {
+            if background != foreground && contrast_ratio(background, foreground) >= min_contrast {
+                color_pairs.push((background, foreground));
+            }
+        }
+    }
+
+    color_pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    // Written under a name whose extension picks the parser
+    fn load(name: &str, contents: &str) -> Result<Palette> {
+        let dir = TempDir::new("tracii").unwrap();
+        let path = dir.path().join(name);
+        File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        Palette::load(&path)
+    }
+
+    #[test]
+    fn the_default_is_the_xterm_table() {
+        let palette = Palette::default();
+        // Entries of the literal table the cube and ramp replaced
+        let table = [
+            (0, [0x00, 0x00, 0x00]),
+            (9, [0xff, 0x00, 0x00]),
+            (16, [0x00, 0x00, 0x00]),
+            (17, [0x00, 0x00, 0x5f]),
+            (100, [0x87, 0x87, 0x00]),
+            (196, [0xff, 0x00, 0x00]),
+            (231, [0xff, 0xff, 0xff]),
+            (232, [0x08, 0x08, 0x08]),
+            (244, [0x80, 0x80, 0x80]),
+            (255, [0xee, 0xee, 0xee]),
+        ];
+        for &(index, color) in &table {
+            assert_eq!(palette.color(index), color, "{}", index);
+        }
+    }
+
+    #[test]
+    fn every_theme_loads() {
+        for name in THEME_NAMES {
+            let palette = Palette::theme(name).unwrap();
+            assert_eq!(palette.colors.len(), 256, "{}", name);
+            // Only the system colors differ between themes
+            assert_eq!(palette.colors[16..], Palette::default().colors[16..], "{}", name);
+        }
+        assert_eq!(Palette::theme("xterm"), Some(Palette::default()));
+        assert_eq!(Palette::theme("solarized").unwrap().color(4), [0x26, 0x8b, 0xd2]);
+        assert_eq!(Palette::theme("nope"), None);
+    }
+
+    #[test]
+    fn colors_parse_in_every_notation() {
+        assert_eq!(parse_color("#268bd2"), Some([0x26, 0x8b, 0xd2]));
+        assert_eq!(parse_color("'0x268bd2'"), Some([0x26, 0x8b, 0xd2]));
+        assert_eq!(parse_color("\"#268BD2\""), Some([0x26, 0x8b, 0xd2]));
+        // X11 scales each channel from however many digits it has
+        assert_eq!(parse_color("rgb:f/0/8"), Some([255, 0, 136]));
+        assert_eq!(parse_color("rgb:ff/00/80"), Some([255, 0, 128]));
+        assert_eq!(parse_color("rgb:fff/000/800"), Some([255, 0, 127]));
+        assert_eq!(parse_color("rgb:ffff/0000/8000"), Some([255, 0, 127]));
+        assert_eq!(parse_color("rgb:fffff/0/0"), None);
+        assert_eq!(parse_color("rgb:f/0"), None);
+        assert_eq!(parse_color("#268bd"), None);
+    }
+
+    #[test]
+    fn itermcolors_read_each_ansi_dict() {
+        let component = |name: &str, value: f32| {
+            format!("<key>{} Component</key>\n<real>{}</real>\n", name, value)
+        };
+        let entry = |index: usize, red: f32, green: f32, blue: f32| {
+            format!(
+                "<key>Ansi {} Color</key>\n<dict>\n<key>Color Space</key>\n<string>sRGB</string>\n{}{}{}</dict>\n",
+                index,
+                component("Blue", blue),
+                component("Green", green),
+                component("Red", red)
+            )
+        };
+        let contents = format!(
+            "<plist version=\"1.0\">\n<dict>\n{}{}{}</dict>\n</plist>\n",
+            entry(1, 1.0, 0.0, 0.0),
+            entry(12, 0.0, 0.5, 1.0),
+            entry(16, 1.0, 1.0, 1.0)
+        );
+
+        let palette = load("theme.itermcolors", &contents).unwrap();
+        assert_eq!(palette.color(1), [255, 0, 0]);
+        assert_eq!(palette.color(12), [0, 128, 255]);
+        // Past the system colors an entry is ignored, and what the file leaves out stays xterm's
+        assert_eq!(palette.colors[16..], Palette::default().colors[16..]);
+        assert_eq!(palette.color(2), Palette::default().color(2));
+    }
+
+    #[test]
+    fn xresources_follow_defines() {
+        let contents = "! Solarized\n\
+                        #define S_blue #268bd2\n\
+                        #define S_red rgb:dc/32/2f\n\
+                        *color4: S_blue\n\
+                        URxvt.color1:   S_red\n\
+                        XTerm*color9: rgb:f/0/0\n\
+                        *.color15: #fdf6e3\n\
+                        *color300: #ffffff\n\
+                        *foreground: #ffffff\n";
+
+        let palette = load(".Xresources", contents).unwrap();
+        assert_eq!(palette.color(4), [0x26, 0x8b, 0xd2]);
+        assert_eq!(palette.color(1), [0xdc, 0x32, 0x2f]);
+        assert_eq!(palette.color(9), [255, 0, 0]);
+        assert_eq!(palette.color(15), [0xfd, 0xf6, 0xe3]);
+        assert_eq!(palette.color(0), Palette::default().color(0));
+    }
+
+    #[test]
+    fn alacritty_reads_yaml_and_toml() {
+        let yaml = "colors:\n  \
+                      primary:\n    \
+                        background: '#000000'\n  \
+                      normal:\n    \
+                        black: '#1d1f21'\n    \
+                        blue:  '0x81a2be'\n  \
+                      bright:\n    \
+                        white: \"#ffffff\"\n";
+        let toml = "[colors.primary]\n\
+                    background = \"#000000\"\n\
+                    \n\
+                    [colors.normal]\n\
+                    black = \"#1d1f21\"\n\
+                    blue = \"0x81a2be\"\n\
+                    \n\
+                    [colors.bright]\n\
+                    white = \"#ffffff\"\n";
+
+        for &(name, contents) in &[("alacritty.yml", yaml), ("alacritty.toml", toml)] {
+            let palette = load(name, contents).unwrap();
+            assert_eq!(palette.color(0), [0x1d, 0x1f, 0x21], "{}", name);
+            assert_eq!(palette.color(4), [0x81, 0xa2, 0xbe], "{}", name);
+            assert_eq!(palette.color(15), [255, 255, 255], "{}", name);
+            // The primary background is not one of the 16
+            assert_eq!(palette.color(8), Palette::default().color(8), "{}", name);
+        }
+    }
+
+    static BLACK: [u8; 3] = [0, 0, 0];
+    static GRAY: [u8; 3] = [0x80, 0x80, 0x80];
+    static WHITE: [u8; 3] = [255, 255, 255];
+
+    #[test]
+    fn a_full_sweep_pairs_every_distinct_color() {
+        let palette = Palette::default();
+        let all: Vec<u8> = (0..=255).collect();
+        let pairs = palette_sweep(&palette, &all, 1.0);
+
+        let mut distinct = palette.colors.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(pairs.len(), distinct.len() * (distinct.len() - 1));
+        assert!(pairs.iter().all(|&(background, foreground)| background != foreground));
+        assert!(pairs.contains(&(palette.color(255), palette.color(0))));
+        assert!(pairs.contains(&(palette.color(254), palette.color(255))));
+    }
+
+    #[test]
+    fn shared_colors_are_swept_once() {
+        // 16 is 0's black and 231 is 15's white
+        let pairs = palette_sweep(&Palette::default(), &[0, 16, 15, 231], 1.0);
+        assert_eq!(pairs, vec![(BLACK, WHITE), (WHITE, BLACK)]);
+    }
+
+    #[test]
+    fn min_contrast_prunes_low_contrast_pairs() {
+        // Black on white is 21, black on gray about 5.3 and gray on white about 3.9
+        let indices = [0, 8, 15];
+        assert_eq!(palette_sweep(&Palette::default(), &indices, 1.0).len(), 6);
+        assert_eq!(
+            palette_sweep(&Palette::default(), &indices, 4.5),
+            vec![(BLACK, GRAY), (BLACK, WHITE), (GRAY, BLACK), (WHITE, BLACK)]
+        );
+        assert_eq!(palette_sweep(&Palette::default(), &indices, 10.0), vec![(BLACK, WHITE), (WHITE, BLACK)]);
+        assert!(palette_sweep(&Palette::default(), &indices, 22.0).is_empty());
+    }
+
+    #[test]
+    fn files_without_colors_are_an_error() {
+        for &name in &["empty.itermcolors", "empty.Xresources", "empty.yml", "empty.toml"] {
+            match load(name, "*foreground: #ffffff\n[colors.primary]\n") {
+                Err(TraciiError::PaletteParse(path)) => assert!(path.ends_with(name)),
+                other => panic!("{} gave {:?}", name, other.map(|_| ())),
+            }
+        }
+    }
+}
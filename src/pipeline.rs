@@ -0,0 +1,138 @@
+use args_and_usage::Args;
+use std::path::Path;
+use tracii::{GlyphAtlas, Options, Pane, PaneLabel, Result, SeededRng, TraciiError};
+
+// A run is a fixed series of stages, each one only done when a flag asks for what it makes:
+//   rasterize      the atlas at the default cell size, for any of the three that follow
+//   export atlas   --exportglyphs, every colorized render to WORKDIR/glyph_renders
+//   benchmark      --benchindex, exhaustive against indexed lookup
+//   contact sheet  --makerenderscramble, a sheet of colorized renders to WORKDIR/scramble.png
+//   convert input  --input, the art to the terminal and WORKDIR, and to html or svg if asked
+// Stages that make random choices draw them in this order from one generator seeded by --seed.
+pub fn run(args: &Args) -> Result<()> {
+    let options = options(args)?;
+    let mut rng = SeededRng::new(args.seed);
+
+    // Converting an input rasterizes its own atlas at the size of the input's tiles
+    if args.export_glyph_renders || args.benchmark_index || args.make_render_scramble {
+        let atlas = tracii::build_atlas(&options)?;
+
+        if args.export_glyph_renders {
+            tracii::export_glyph_renders(&args.work_dir, &atlas)?;
+        }
+
+        if args.benchmark_index {
+            benchmark(&options, &atlas)?;
+        }
+
+        if args.make_render_scramble {
+            contact_sheet(args, &atlas, &mut rng)?;
+        }
+    } else if args.input.is_none() {
+        println!("Nothing to do, pass an --input to convert or ask for --exportglyphs, \
+                  --benchindex or --makerenderscramble");
+    }
+
+    if let Some(ref input_path) = args.input {
+        convert_input(args, &options, input_path)?;
+    }
+
+    println!("Work dir: {}", args.work_dir.to_string_lossy());
+    Ok(())
+}
+
+// Everything the library needs to know from the arguments
+fn options(args: &Args) -> Result<Options> {
+    let mut options = Options::new(args.font_path.clone());
+    options.font_index = args.font_index;
+    options.fallback_fonts = args.fallback_fonts.clone();
+    options.cell_ratio = args.cell_ratio;
+    options.overflow = args.overflow;
+    options.columns = args.columns;
+    options.rows = args.rows;
+    options.size_policy = args.size_policy;
+    options.mode = args.mode;
+    options.metric = args.metric.clone();
+    options.indexed = args.indexed;
+    options.cache_dir = Some(args.cache_dir.clone());
+    options.rebuild_atlas = args.rebuild_atlas;
+    options.palette = args.palette.clone();
+
+    if args.limited_char_set {
+        options.chars = (45..50).map(From::from).collect();
+    }
+
+    if let Some(ref chars) = args.chars {
+        options.chars = chars.clone();
+    }
+
+    if args.color_256 {
+        options.color_pairs = tracii::palette_sweep(&options.palette, &args.palette_indices, args.min_contrast);
+        if options.color_pairs.is_empty() {
+            return Err(TraciiError::InvalidArgument(format!(
+                "No pair of the swept colors has a contrast ratio of {} or more",
+                args.min_contrast
+            )));
+        }
+        println!("Sweeping {} color pairs", options.color_pairs.len());
+    }
+
+    Ok(options)
+}
+
+fn benchmark(options: &Options, atlas: &GlyphAtlas) -> Result<()> {
+    let columns = options.columns.unwrap_or(tracii::DEFAULT_COLUMNS);
+    tracii::benchmark_index(atlas, columns, tracii::cell_ratio(options)?);
+    Ok(())
+}
+
+fn contact_sheet(args: &Args, atlas: &GlyphAtlas, rng: &mut SeededRng) -> Result<()> {
+    // Only the renders that make it onto the sheet are drawn
+    let renders: Vec<_> = tracii::sheet_order(atlas.len(), &args.sheet, rng)
+        .into_iter()
+        .map(|index| (atlas.render(index), atlas.parts(index)))
+        .collect();
+    let panes: Vec<_> = renders
+        .iter()
+        .map(|&(ref render, (mask, background, foreground))| Pane {
+            image: &render.buffer,
+            label: Some(PaneLabel {
+                c: mask.c,
+                background,
+                foreground,
+            }),
+        })
+        .collect();
+
+    if let Some(sheet) = tracii::contact_sheet(&panes, &args.sheet)? {
+        let path = args.work_dir.join("scramble.png");
+        sheet.save(&path).map_err(|error| TraciiError::ImageSave { path, error })?;
+    }
+    Ok(())
+}
+
+fn convert_input(args: &Args, options: &Options, input_path: &Path) -> Result<()> {
+    let input = tracii::load_input(input_path)?;
+    let atlas = tracii::build_atlas_for(&input, options)?;
+    let art = tracii::convert_with_atlas(&input, &atlas, options)?;
+    print!("{}", tracii::render_ansi(&art, args.output_format, &options.palette));
+    art.export(&args.work_dir, &atlas)?;
+
+    let fonts = options.font_chain();
+
+    if args.export_html {
+        tracii::export_web(
+            &args.work_dir.join("ascii.html"),
+            &tracii::to_html(&art, &fonts)
+        )?;
+    }
+
+    if args.export_svg {
+        tracii::export_web(
+            &args.work_dir.join("ascii.svg"),
+            &tracii::to_svg(&art, &fonts, tracii::cell_ratio(options)?)
+        )?;
+    }
+
+    Ok(())
+}
@@ -1,200 +1,704 @@
-use image::{ImageBuffer, Rgb};
+use image::{imageops, FilterType, ImageBuffer, Luma, Rgb, RgbImage};
 use std::fs::{create_dir, File};
 use std::io::Read;
-use std::path::Path;
-use std::process::exit;
-use rusttype::{Font, Glyph, FontCollection, Point, Rect, Scale, SharedBytes};
+use std::path::{Path, PathBuf};
+use error::{Result, TraciiError};
+use font_discovery::face_offsets;
+use rusttype::{Font, Glyph, FontCollection, Point, PositionedGlyph, Rect, Scale, SharedBytes};
 
-pub fn load_glyphs(font_path: &Path, chars_to_render: &Vec<char>) -> Vec<(char, Glyph<'static>)> {
-    // First we read in the file into a byte buffer
-    let mut font_file = File::open(font_path).unwrap();
+// A font file to draw glyphs from, and which face of it when it is a collection
+#[derive(Clone, Debug)]
+pub struct FontSource {
+    pub path: PathBuf,
+    pub index: Option<usize>,
+}
+
+impl FontSource {
+    // Every face of a font file, so a collection can stand in the fallback chain face by face
+    pub fn faces(path: &Path) -> Result<Vec<FontSource>> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|error| TraciiError::Io { path: path.to_path_buf(), error })?;
+        let count = face_offsets(&bytes).ok_or_else(|| TraciiError::FontParse(path.to_path_buf()))?.len();
+
+        if count == 1 {
+            return Ok(vec![FontSource {
+                path: path.to_path_buf(),
+                index: None,
+            }]);
+        }
+        Ok((0..count)
+            .map(|index| FontSource {
+                path: path.to_path_buf(),
+                index: Some(index),
+            })
+            .collect())
+    }
+}
+
+pub static OVERFLOW_NAMES: &[&str] = &["clip", "scale-to-fit", "skip"];
+
+// What happens to a glyph that reaches outside its cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    Clip,
+    ScaleToFit,
+    Skip,
+}
+
+impl Overflow {
+    pub fn from_name(name: &str) -> Option<Overflow> {
+        match name {
+            "clip" => Some(Overflow::Clip),
+            "scale-to-fit" => Some(Overflow::ScaleToFit),
+            "skip" => Some(Overflow::Skip),
+            _ => None,
+        }
+    }
+}
+
+// The primary font's vertical metrics and the advance of its cells, at the scale where
+// ascent - descent is 1. This is the geometry a terminal lays its grid out with.
+#[derive(Clone, Copy, Debug)]
+pub struct CellMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+    pub advance_width: f32,
+}
+
+impl CellMetrics {
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    // The h/w ratio of a cell in this font
+    pub fn ratio(&self) -> f32 {
+        self.line_height() / self.advance_width
+    }
+}
+
+pub fn cell_metrics(source: &FontSource) -> Result<CellMetrics> {
     let mut byte_buffer = Vec::new();
-    if let Err(read_error) = font_file.read_to_end(&mut byte_buffer) {
-        println!("There was an error reading from {}\n{}",
-                 font_path.to_string_lossy(),
-                 read_error);
-        exit(2);
+    File::open(&source.path)
+        .and_then(|mut font_file| font_file.read_to_end(&mut byte_buffer))
+        .map_err(|error| TraciiError::Io { path: source.path.clone(), error })?;
+    let font = load_font(source, &byte_buffer)?;
+
+    let unit = Scale::uniform(1.0);
+    let v_metrics = font.v_metrics(unit);
+    // Monospace fonts share one advance, M is the traditional em to measure it by
+    let advance_width = font
+        .glyph('M')
+        .map_or(0.0, |glyph| glyph.scaled(unit).h_metrics().advance_width);
+    if advance_width <= 0.0 {
+        return Err(TraciiError::NoAdvanceWidth(source.path.clone()));
     }
 
-    // Then we convert that byte buffer into a Vec of Fonts
-    let mut fonts_in_file: Vec<Font> = FontCollection::from_bytes(SharedBytes::ByRef(&byte_buffer))
-        .into_fonts()
-        .collect();
+    Ok(CellMetrics {
+        ascent: v_metrics.ascent,
+        descent: v_metrics.descent,
+        line_gap: v_metrics.line_gap,
+        advance_width,
+    })
+}
 
-    // For now we can only handle having one font in the file
-    if fonts_in_file.len() == 0 {
-        println!("There were no fonts in {}", font_path.to_string_lossy());
-        exit(2);
+// Each char is drawn from the first font in the chain that has a glyph for it.
+// The usize is the position of that font in the chain, 0 being the primary font.
+pub fn load_glyphs(
+    fonts: &[FontSource],
+    chars_to_render: &Vec<char>
+) -> Result<Vec<(char, Glyph<'static>, usize)>> {
+    let mut byte_buffers = Vec::new();
+    for source in fonts {
+        // First we read in the file into a byte buffer
+        let mut byte_buffer = Vec::new();
+        File::open(&source.path)
+            .and_then(|mut font_file| font_file.read_to_end(&mut byte_buffer))
+            .map_err(|error| TraciiError::Io { path: source.path.clone(), error })?;
+        byte_buffers.push(byte_buffer);
     }
-    if fonts_in_file.len() > 1 {
-        println!("There was more than one font in {}",
-                 font_path.to_string_lossy());
-        exit(2);
+
+    let mut chain = Vec::new();
+    for (source, byte_buffer) in fonts.iter().zip(&byte_buffers) {
+        chain.push(load_font(source, byte_buffer)?);
     }
-    let font = fonts_in_file.remove(0);
 
-    // Now we extract the glyphs for the characters we want to render with
-    // We emit a warning if we couldn't extract a glyph for a character
+    // Now we extract the glyphs for the characters we want to render with.
+    // A font without an outline for the char hands back glyph 0, the .notdef box.
     let mut glyphs = Vec::new();
     for c in chars_to_render {
-        let result = font.glyph(c.clone());
+        let found = chain
+            .iter()
+            .enumerate()
+            .filter_map(|(source, font)| font.glyph(*c).map(|glyph| (source, glyph)))
+            .find(|(_, glyph)| glyph.id().0 != 0);
 
-        let glyph = match result {
-            Some(glyph) => glyph,
-            None => {
-                println!("WARN: There was an error loading the glyph for {}", c);
-                continue;
+        // Whitespace is drawn as plain background, so it never needs a font to have it
+        match found {
+            Some((source, glyph)) => glyphs.push((*c, glyph.standalone(), source)),
+            None if c.is_whitespace() => {
+                if let Some(glyph) = chain[0].glyph(*c) {
+                    glyphs.push((*c, glyph.standalone(), 0));
+                }
             }
-        };
+            None => {}
+        }
+    }
+
+    Ok(glyphs)
+}
 
-        glyphs.push((c.clone(), glyph.standalone()));
+fn load_font<'a>(source: &FontSource, byte_buffer: &'a [u8]) -> Result<Font<'a>> {
+    let collection = FontCollection::from_bytes(SharedBytes::ByRef(byte_buffer));
+
+    // A collection needs to be told which of its faces to use
+    match source.index {
+        Some(index) => collection.font_at(index).ok_or_else(|| TraciiError::FaceNotFound {
+            path: source.path.clone(),
+            face: format!("at index {}", index),
+        }),
+        None => {
+            let mut fonts_in_file: Vec<Font> = collection.into_fonts().collect();
+            if fonts_in_file.is_empty() {
+                return Err(TraciiError::FontParse(source.path.clone()));
+            }
+            if fonts_in_file.len() > 1 {
+                return Err(TraciiError::MultipleFontsInFile(source.path.clone()));
+            }
+            Ok(fonts_in_file.remove(0))
+        }
     }
+}
 
-    glyphs
+// The chars no font in the chain had a glyph for
+pub fn uncovered_chars(chars: &[char], masks: &[GlyphMask]) -> Vec<char> {
+    chars
+        .iter()
+        .filter(|c| !masks.iter().any(|mask| mask.c == **c))
+        .cloned()
+        .collect()
 }
 
+// Rasterize every glyph into a (width, height) pixel cell. A line of the font fills the
+// cell's height, and an advance its width. When the cell is not the font's own shape,
+// the glyphs are stretched horizontally to keep filling it.
 pub fn render_glyphs(
-    glyphs: &Vec<(char, Glyph)>,
-    renders: &mut Vec<GlyphRender>,
-    background: [u8; 3],
-    foreground: [u8; 3],
-    height: u32,
-    ratio: f32
-) {
-    let width = (height as f32 / ratio) as u32;
-
-    // Now we transform the glyphs to GlyphRenders (fancy image buffers)
-    for &(ref c, ref glyph) in glyphs {
-        // The Glyph needs scale and position information
-        let positioned_glyph = glyph.standalone()
-            .scaled(Scale { x: 40.0, y: 80.0 })
-            .positioned(Point { x: 0.0, y: 0.0 });
-
-        // The renderer needs information about the scaled glyph
+    glyphs: &Vec<(char, Glyph, usize)>,
+    metrics: &CellMetrics,
+    cell_size: (u32, u32),
+    overflow: Overflow
+) -> Result<Vec<GlyphMask>> {
+    let (width, height) = (cell_size.0.max(1), cell_size.1.max(1));
+    let scale = Scale {
+        x: width as f32 / metrics.advance_width,
+        y: height as f32 / metrics.line_height(),
+    };
+    // Every glyph sits on the same baseline, with the line gap split above and below
+    let baseline = scale.y * (metrics.line_gap / 2.0 + metrics.ascent);
+    let mut masks = Vec::new();
+
+    // Now we transform the glyphs to GlyphMasks, the color comes later
+    for &(ref c, ref glyph, source) in glyphs {
+        // Spaces are a cell of solid background, whatever the font puts there
+        if c.is_whitespace() {
+            masks.push(GlyphRenderer::new(EMPTY_BOX, height, width, *c, source).finalize());
+            continue;
+        }
+
+        // The Glyph needs scale and position information. Fallback glyphs whose
+        // advance differs from the primary font's are centered in the cell.
+        let scaled_glyph = glyph.standalone().scaled(scale);
+        let advance_width = scaled_glyph.h_metrics().advance_width;
+        let mut positioned_glyph = scaled_glyph.positioned(Point {
+            x: (width as f32 - advance_width) / 2.0,
+            y: baseline,
+        });
+
+        // The renderer needs information about the scaled glyph.
+        // Glyphs without an outline have no box, they are left empty.
+        let mut bounding_box = positioned_glyph.pixel_bounding_box().unwrap_or(EMPTY_BOX);
+
+        // Boxes are rounded out to whole pixels, so glyphs made to fill the cell,
+        // like the blocks, spill over by one
+        let overflows = bounding_box.min.x < -1
+            || bounding_box.min.y < -1
+            || bounding_box.max.x > width as i32 + 1
+            || bounding_box.max.y > height as i32 + 1;
+        if overflows {
+            match overflow {
+                Overflow::Clip => {}
+                Overflow::Skip => {
+                    eprintln!("WARN: {} does not fit in a {}x{} cell, skipping it", c, width, height);
+                    continue;
+                }
+                Overflow::ScaleToFit => {
+                    positioned_glyph = fit_to_cell(glyph, scale, &bounding_box, width, height);
+                    bounding_box = positioned_glyph.pixel_bounding_box().unwrap_or(EMPTY_BOX);
+                }
+            }
+        }
+
         let mut renderer = GlyphRenderer::new(
-            positioned_glyph.pixel_bounding_box().unwrap(),
-            Rgb { data: background },
-            Rgb { data: foreground },
+            bounding_box,
             height,
             width,
-            c.clone()
+            *c,
+            source
         );
 
         // Now draw it and push the result
-        positioned_glyph.draw(&mut renderer);
-        renders.push(renderer.finalize());
+        positioned_glyph.draw(|x, y, coverage| renderer.plot(x, y, coverage));
+        masks.push(renderer.finalize());
     }
 
+    Ok(masks)
 }
 
-pub fn export_glyph_renders(work_dir: &Path, glyph_renders: &Vec<GlyphRender>) {
-    let mut index = 0;
-    let render_dir = work_dir.join("glyph_renders");
+static EMPTY_BOX: Rect<i32> = Rect {
+    min: Point { x: 0, y: 0 },
+    max: Point { x: 0, y: 0 },
+};
 
-    if let Err(error) = create_dir(&render_dir) {
-        println!("There was an error making the render dir {}:\n{}",
-                 render_dir.to_string_lossy(),
-                 error);
-        exit(3);
-    }
+// The glyph shrunk until its box fits the cell, and centered in it. It leaves the baseline,
+// but a glyph this big was never going to sit on it.
+fn fit_to_cell(
+    glyph: &Glyph,
+    scale: Scale,
+    bounding_box: &Rect<i32>,
+    width: u32,
+    height: u32
+) -> PositionedGlyph<'static> {
+    let box_width = (bounding_box.max.x - bounding_box.min.x).max(1) as f32;
+    let box_height = (bounding_box.max.y - bounding_box.min.y).max(1) as f32;
+    let shrink = (width as f32 / box_width).min(height as f32 / box_height).min(1.0);
 
-    for render in glyph_renders {
-        let render_path = render_dir.join(format!("{}.png", index));
-        render.export(&render_path);
-        index += 1;
-    }
-}
+    let scaled_glyph = glyph.standalone().scaled(Scale {
+        x: scale.x * shrink,
+        y: scale.y * shrink,
+    });
+    let shrunk_box = scaled_glyph
+        .standalone()
+        .positioned(Point { x: 0.0, y: 0.0 })
+        .pixel_bounding_box()
+        .unwrap_or(EMPTY_BOX);
 
-struct GlyphRenderer {
-    buffer: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    background: Rgb<u8>,
-    foreground: Rgb<u8>,
-    background_f: [f32; 3],
-    foreground_f: [f32; 3],
-    x_offset: u32,
-    y_offset: u32,
-    c: char,
+    scaled_glyph.positioned(Point {
+        x: (width as f32 - (shrunk_box.max.x - shrunk_box.min.x) as f32) / 2.0 - shrunk_box.min.x as f32,
+        y: (height as f32 - (shrunk_box.max.y - shrunk_box.min.y) as f32) / 2.0 - shrunk_box.min.y as f32,
+    })
 }
 
-impl FnOnce<(u32, u32, f32)> for GlyphRenderer {
-    type Output = ();
-    extern "rust-call" fn call_once(self, _: (u32, u32, f32)) -> () {}
-}
+pub fn export_glyph_renders(work_dir: &Path, atlas: &GlyphAtlas) -> Result<()> {
+    let render_dir = work_dir.join("glyph_renders");
 
-impl FnMut<(u32, u32, f32)> for GlyphRenderer {
-    extern "rust-call" fn call_mut(&mut self, args: (u32, u32, f32)) -> () {
-        let t_r = (self.foreground_f[0] * args.2 + self.background_f[0] * (1.0 - args.2)) as u8;
-        let t_g = (self.foreground_f[1] * args.2 + self.background_f[1] * (1.0 - args.2)) as u8;
-        let t_b = (self.foreground_f[2] * args.2 + self.background_f[2] * (1.0 - args.2)) as u8;
+    create_dir(&render_dir)
+        .map_err(|error| TraciiError::Export { path: render_dir.clone(), error })?;
 
-        self.buffer.put_pixel(args.0 + self.x_offset,
-                              args.1 + self.y_offset,
-                              Rgb { data: [t_r, t_g, t_b] });
+    // Renders are named by codepoint, and by color pair when there is more than one
+    for index in 0..atlas.len() {
+        let render = atlas.render(index);
+        let pair = index / atlas.masks.len();
+        let name = if atlas.color_pairs.len() == 1 {
+            format!("U+{:04X}.png", render.c as u32)
+        } else {
+            format!("U+{:04X}-{}.png", render.c as u32, pair)
+        };
+        render.export(&render_dir.join(name))?;
     }
+
+    Ok(())
+}
+
+struct GlyphRenderer {
+    coverage: Vec<f32>,
+    width: u32,
+    height: u32,
+    x_offset: i32,
+    y_offset: i32,
+    c: char,
+    source: usize,
 }
 
 impl GlyphRenderer {
     fn new(bounding_box: Rect<i32>,
-           background: Rgb<u8>,
-           foreground: Rgb<u8>,
            height: u32,
            width: u32,
-           c: char)
+           c: char,
+           source: usize)
            -> GlyphRenderer {
-        let background_f = [background[0].clone() as f32,
-                            background[1].clone() as f32,
-                            background[2].clone() as f32];
+        // The glyph was positioned in cell coordinates, its box says where it landed
+        let x_offset = bounding_box.min.x;
+        let y_offset = bounding_box.min.y;
 
-        let foreground_f = [foreground[0].clone() as f32,
-                            foreground[1].clone() as f32,
-                            foreground[2].clone() as f32];
+        GlyphRenderer {
+            coverage: vec![0.0; (width * height) as usize],
+            width,
+            height,
+            x_offset,
+            y_offset,
+            c,
+            source,
+        }
+    }
 
-        let buffer = ImageBuffer::from_pixel(width, height, background);
+    // Called by rusttype with each pixel of the glyph's box and how much of it is covered
+    fn plot(&mut self, x: u32, y: u32, coverage: f32) {
+        // Glyphs bigger than the cell, like full blocks, are clipped to it
+        let x = x as i32 + self.x_offset;
+        let y = y as i32 + self.y_offset;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        self.coverage[(x as u32 + y as u32 * self.width) as usize] = coverage;
+    }
 
-        let bb_width = (bounding_box.max.x - bounding_box.min.x) as u32;
-        let bb_height = (bounding_box.max.y - bounding_box.min.y) as u32;
+    fn finalize(self) -> GlyphMask {
+        GlyphMask {
+            coverage: self.coverage,
+            width: self.width,
+            height: self.height,
+            c: self.c,
+            source: self.source,
+        }
+    }
+}
 
-        let x_offset = (width - bb_width) / 2;
-        let y_offset = (height - bb_height) / 2;
+// How much of each pixel in a cell the glyph covers, from 0.0 to 1.0
+pub struct GlyphMask {
+    pub coverage: Vec<f32>,
+    pub width: u32,
+    pub height: u32,
+    pub c: char,
+    // Which font in the chain the glyph came from, 0 being the primary font
+    pub source: usize,
+}
 
-        GlyphRenderer {
-            buffer: buffer,
-            background_f: background_f,
-            foreground_f: foreground_f,
-            background: background,
-            foreground: foreground,
-            x_offset: x_offset,
-            y_offset: y_offset,
-            c: c,
+impl GlyphMask {
+    pub fn colorize(&self, background: [u8; 3], foreground: [u8; 3]) -> GlyphRender {
+        let mut buffer = ImageBuffer::new(self.width, self.height);
+        self.colorize_into(background, foreground, &mut buffer);
+
+        GlyphRender {
+            buffer,
+            background: Rgb { data: background },
+            foreground: Rgb { data: foreground },
+            c: self.c,
         }
     }
 
-    fn finalize(self) -> GlyphRender {
-        GlyphRender {
-            buffer: self.buffer,
-            background: self.background,
-            foreground: self.foreground,
+    // Colorize over a buffer the size of the mask, so one buffer can be reused for many renders
+    pub fn colorize_into(&self, background: [u8; 3], foreground: [u8; 3], buffer: &mut RgbImage) {
+        for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+            let alpha = self.coverage[(x + y * self.width) as usize];
+            for channel in 0..3 {
+                pixel.data[channel] = (foreground[channel] as f32 * alpha
+                    + background[channel] as f32 * (1.0 - alpha)) as u8;
+            }
+        }
+    }
+
+    // The same mask resampled to a different cell size
+    pub fn resize(&self, width: u32, height: u32) -> GlyphMask {
+        if width == self.width && height == self.height {
+            return GlyphMask {
+                coverage: self.coverage.clone(),
+                width,
+                height,
+                c: self.c,
+                source: self.source,
+            };
+        }
+
+        let image: ImageBuffer<Luma<u8>, Vec<u8>> = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Luma { data: [(self.coverage[(x + y * self.width) as usize] * 255.0).round() as u8] }
+        });
+        let resized = imageops::resize(&image, width, height, FilterType::Triangle);
+
+        GlyphMask {
+            coverage: resized.pixels().map(|pixel| pixel.data[0] as f32 / 255.0).collect(),
+            width,
+            height,
             c: self.c,
+            source: self.source,
         }
     }
 }
 
+// Every mask in every color pair, without keeping a colored buffer for each of them.
+// Render i is mask (i % masks.len()) in color pair (i / masks.len()).
+pub struct GlyphAtlas {
+    pub masks: Vec<GlyphMask>,
+    pub color_pairs: Vec<([u8; 3], [u8; 3])>,
+}
+
+impl GlyphAtlas {
+    pub fn len(&self) -> usize {
+        self.masks.len() * self.color_pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The mask and (background, foreground) pair behind render index
+    pub fn parts(&self, index: usize) -> (&GlyphMask, [u8; 3], [u8; 3]) {
+        let (background, foreground) = self.color_pairs[index / self.masks.len()];
+        (&self.masks[index % self.masks.len()], background, foreground)
+    }
+
+    pub fn render(&self, index: usize) -> GlyphRender {
+        let (mask, background, foreground) = self.parts(index);
+        mask.colorize(background, foreground)
+    }
+}
+
 pub struct GlyphRender {
     pub buffer: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    background: Rgb<u8>,
-    foreground: Rgb<u8>,
-    c: char,
+    pub background: Rgb<u8>,
+    pub foreground: Rgb<u8>,
+    pub c: char,
 }
 
 impl GlyphRender {
-    fn export(&self, path: &Path) {
-        let result = self.buffer.save(path);
-
-        if let Err(error) = result {
-            println!("There was an error saving the GlyphRender for {}:\n{}",
-                     self.c,
-                     error);
-            exit(3);
+    pub fn export(&self, path: &Path) -> Result<()> {
+        self.buffer
+            .save(path)
+            .map_err(|error| TraciiError::ImageSave { path: path.to_path_buf(), error })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+    use tests::{collection, fixture};
+
+    static POLICIES: [Overflow; 3] = [Overflow::Clip, Overflow::ScaleToFit, Overflow::Skip];
+
+    // A cell with a ratio of 3, much narrower than DejaVu Sans Mono's own of about 2
+    static NARROW_CELL: (u32, u32) = (20, 60);
+
+    fn masks(chars: &str, cell_size: (u32, u32), overflow: Overflow) -> Vec<GlyphMask> {
+        let fonts = [FontSource {
+            path: fixture("DejaVuSansMono.ttf"),
+            index: None,
+        }];
+        let metrics = cell_metrics(&fonts[0]).unwrap();
+        let glyphs = load_glyphs(&fonts, &chars.chars().collect()).unwrap();
+        render_glyphs(&glyphs, &metrics, cell_size, overflow).unwrap()
+    }
+
+    // The (left, top, right, bottom) pixels with any coverage, inclusive
+    fn ink_bounds(mask: &GlyphMask) -> Option<(u32, u32, u32, u32)> {
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                if mask.coverage[(x + y * mask.width) as usize] > 0.0 {
+                    bounds = Some(match bounds {
+                        Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                        None => (x, y, x, y),
+                    });
+                }
+            }
+        }
+        bounds
+    }
+
+    #[test]
+    fn wide_glyphs_render_in_narrow_cells_under_every_policy() {
+        for &overflow in &POLICIES {
+            let masks = masks("WM@", NARROW_CELL, overflow);
+            assert_eq!(masks.len(), 3, "{:?}", overflow);
+            for mask in &masks {
+                assert_eq!((mask.width, mask.height), NARROW_CELL);
+                let (left, top, right, bottom) = ink_bounds(mask).unwrap();
+                // They are stretched to the cell's width, and sit on the baseline below its middle
+                assert!(left <= 1 && right >= NARROW_CELL.0 - 2, "{} {:?}", mask.c, overflow);
+                assert!(top > 0 && bottom < NARROW_CELL.1 - 1, "{} {:?}", mask.c, overflow);
+            }
+        }
+    }
+
+    // Ǘ stacks two accents over a capital, taller than a line of the font
+    #[test]
+    fn clip_cuts_overflowing_glyphs_at_the_cell_edge() {
+        let masks = masks("Ǘ", NARROW_CELL, Overflow::Clip);
+        assert_eq!(ink_bounds(&masks[0]).unwrap().1, 0);
+    }
+
+    #[test]
+    fn scale_to_fit_keeps_all_of_an_overflowing_glyph() {
+        let masks = masks("Ǘ", NARROW_CELL, Overflow::ScaleToFit);
+        let (_, top, _, bottom) = ink_bounds(&masks[0]).unwrap();
+        assert!(top > 0 && bottom < NARROW_CELL.1 - 1);
+    }
+
+    #[test]
+    fn skip_drops_overflowing_glyphs() {
+        let masks = masks("WǗ", NARROW_CELL, Overflow::Skip);
+        let chars: Vec<char> = masks.iter().map(|mask| mask.c).collect();
+        assert_eq!(chars, vec!['W']);
+    }
+
+    #[test]
+    fn spaces_are_empty_cells() {
+        for &overflow in &POLICIES {
+            let masks = masks(" ", NARROW_CELL, overflow);
+            assert_eq!(masks.len(), 1);
+            assert_eq!((masks[0].width, masks[0].height), NARROW_CELL);
+            assert_eq!(ink_bounds(&masks[0]), None);
+        }
+    }
+
+    fn read_u16(bytes: &[u8], at: usize) -> usize {
+        (bytes[at] as usize) << 8 | bytes[at + 1] as usize
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> usize {
+        read_u16(bytes, at) << 16 | read_u16(bytes, at + 2)
+    }
+
+    fn write_u16(bytes: &mut [u8], at: usize, value: usize) {
+        bytes[at] = (value >> 8) as u8;
+        bytes[at + 1] = value as u8;
+    }
+
+    fn write_u32(bytes: &mut [u8], at: usize, value: usize) {
+        write_u16(bytes, at, value >> 16);
+        write_u16(bytes, at + 2, value & 0xffff);
+    }
+
+    // A copy of a font whose cmap no longer maps the chars from the start of the range that
+    // holds last up to last itself, so they come back as glyph 0. Only the format 4 and 12
+    // subtables are cut, which is all a font like DejaVu has for them.
+    fn without_chars_up_to(font: &Path, last: char, path: &Path) {
+        let mut bytes = Vec::new();
+        File::open(font).unwrap().read_to_end(&mut bytes).unwrap();
+        let last = last as usize;
+
+        let cmap = (0..read_u16(&bytes, 4))
+            .map(|table| 12 + table * 16)
+            .find(|&record| &bytes[record..record + 4] == b"cmap")
+            .map(|record| read_u32(&bytes, record + 8))
+            .unwrap();
+        for encoding in 0..read_u16(&bytes, cmap + 2) {
+            let subtable = cmap + read_u32(&bytes, cmap + 4 + encoding * 8 + 4);
+            match read_u16(&bytes, subtable) {
+                4 => {
+                    let segments = read_u16(&bytes, subtable + 6) / 2;
+                    for segment in 0..segments {
+                        let end = subtable + 14 + segment * 2;
+                        let start = end + segments * 2 + 2;
+                        // A segment without a range offset maps each char to char + delta,
+                        // which does not depend on where it starts
+                        if read_u16(&bytes, start) <= last && read_u16(&bytes, end) > last {
+                            assert_eq!(read_u16(&bytes, start + segments * 4), 0);
+                            write_u16(&mut bytes, start, last + 1);
+                        }
+                    }
+                }
+                12 => {
+                    for group in 0..read_u32(&bytes, subtable + 12) {
+                        let start = subtable + 16 + group * 12;
+                        let first = read_u32(&bytes, start);
+                        if first <= last && read_u32(&bytes, start + 4) > last {
+                            let glyph = read_u32(&bytes, start + 8) + last + 1 - first;
+                            write_u32(&mut bytes, start, last + 1);
+                            write_u32(&mut bytes, start + 8, glyph);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        File::create(path).unwrap().write_all(&bytes).unwrap();
+    }
+
+    #[test]
+    fn chars_the_primary_lacks_come_from_a_fallback() {
+        let dir = TempDir::new("tracii").unwrap();
+        let primary = dir.path().join("no-capital-a.ttf");
+        without_chars_up_to(&fixture("DejaVuSansMono.ttf"), 'A', &primary);
+        let chain = [
+            FontSource { path: primary, index: None },
+            FontSource { path: fixture("DejaVuSansMono.ttf"), index: None },
+        ];
+
+        // No font has a glyph for a private use code point
+        let chars = vec!['A', 'B', '@', 'a', '\u{10FFFD}'];
+        let glyphs = load_glyphs(&chain, &chars).unwrap();
+        let sources: Vec<(char, usize)> = glyphs.iter().map(|&(c, _, source)| (c, source)).collect();
+        assert_eq!(sources, vec![('A', 1), ('B', 0), ('@', 1), ('a', 0)]);
+
+        let metrics = cell_metrics(&chain[0]).unwrap();
+        let masks = render_glyphs(&glyphs, &metrics, (12, 24), Overflow::Clip).unwrap();
+        let sources: Vec<(char, usize)> = masks.iter().map(|mask| (mask.c, mask.source)).collect();
+        assert_eq!(sources, vec![('A', 1), ('B', 0), ('@', 1), ('a', 0)]);
+        assert_eq!(uncovered_chars(&chars, &masks), vec!['\u{10FFFD}']);
+
+        // Without the fallback the primary's gaps are uncovered too
+        let glyphs = load_glyphs(&chain[..1], &chars).unwrap();
+        let masks = render_glyphs(&glyphs, &metrics, (12, 24), Overflow::Clip).unwrap();
+        assert_eq!(uncovered_chars(&chars, &masks), vec!['A', '@', '\u{10FFFD}']);
+    }
+
+    #[test]
+    fn collections_fall_back_face_by_face() {
+        let dir = TempDir::new("tracii").unwrap();
+        let path = dir.path().join("mono.ttc");
+        collection(&fixture("DejaVuSansMono.ttf"), 2, &path);
+
+        let faces = FontSource::faces(&path).unwrap();
+        let indices: Vec<Option<usize>> = faces.iter().map(|face| face.index).collect();
+        assert_eq!(indices, vec![Some(0), Some(1)]);
+
+        let single = FontSource::faces(&fixture("DejaVuSansMono.ttf")).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].index, None);
+
+        // Chars the primary lacks come from the collection's first face
+        let primary = dir.path().join("no-capital-a.ttf");
+        without_chars_up_to(&fixture("DejaVuSansMono.ttf"), 'A', &primary);
+        let mut chain = vec![FontSource { path: primary, index: None }];
+        chain.extend(faces);
+        let glyphs = load_glyphs(&chain, &vec!['A', 'a']).unwrap();
+        let sources: Vec<(char, usize)> = glyphs.iter().map(|&(c, _, source)| (c, source)).collect();
+        assert_eq!(sources, vec![('A', 1), ('a', 0)]);
+    }
+
+    #[test]
+    fn dejavu_cells_are_about_twice_as_tall_as_wide() {
+        let metrics = cell_metrics(&FontSource {
+            path: fixture("DejaVuSansMono.ttf"),
+            index: None,
+        }).unwrap();
+        // An ascent of 1901 and descent of 483 font units over an advance of 1233
+        assert!((metrics.ratio() - 2384.0 / 1233.0).abs() < 0.001, "{}", metrics.ratio());
+    }
+
+    #[test]
+    fn glyphs_share_a_baseline() {
+        let masks = masks("Tg", (24, 48), Overflow::Clip);
+        let (_, t_top, _, baseline) = ink_bounds(&masks[0]).unwrap();
+        let (_, g_top, _, g_bottom) = ink_bounds(&masks[1]).unwrap();
+
+        // The bowl of g rests on the row T stands on, and its tail hangs below it
+        let g_row = |y: u32| (0..24).any(|x| masks[1].coverage[(x + y * 24) as usize] > 0.5);
+        assert!(g_row(baseline) || g_row(baseline - 1));
+        assert!(g_bottom > baseline + 4);
+        assert!(t_top < g_top);
+    }
+
+    #[test]
+    fn tiny_cells_do_not_panic() {
+        for &overflow in &POLICIES {
+            for mask in masks("WM@Ǘ █", (1, 3), overflow) {
+                assert_eq!((mask.width, mask.height), (1, 3));
+            }
         }
     }
 }
@@ -0,0 +1,26 @@
+use rand::{ChaChaRng, Rng, SeedableRng};
+
+// Every random choice is drawn from one of these, made from the run's seed and handed to
+// whatever needs it, so the same seed always gives the same artifacts. ChaCha's stream is
+// the same on every platform, unlike the generators rand picks by default.
+pub struct SeededRng {
+    rng: ChaChaRng,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng {
+            rng: ChaChaRng::from_seed(&[seed as u32, (seed >> 32) as u32]),
+        }
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+}
@@ -0,0 +1,39 @@
+use libc;
+use std::env;
+
+// The (columns, rows) of the terminal, from the tty itself or else what the shell exported
+pub fn terminal_size() -> Option<(u32, u32)> {
+    tty_size().or_else(env_size)
+}
+
+#[cfg(unix)]
+fn tty_size() -> Option<(u32, u32)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    // Output that is piped or redirected has no size to ask for
+    if result == 0 && size.ws_col > 0 && size.ws_row > 0 {
+        Some((size.ws_col as u32, size.ws_row as u32))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn tty_size() -> Option<(u32, u32)> {
+    None
+}
+
+fn env_size() -> Option<(u32, u32)> {
+    let dimension = |name| {
+        env::var(name)
+            .ok()
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .filter(|&value| value > 0)
+    };
+    Some((dimension("COLUMNS")?, dimension("LINES")?))
+}
@@ -0,0 +1,391 @@
+use convert::{AsciiArt, Cell};
+use error::{Result, TraciiError};
+use font_discovery::read_font_info;
+use render_glyphs::FontSource;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+// The width of an SVG cell in user units, the height follows from the cell ratio
+static SVG_CELL_WIDTH: f32 = 10.0;
+
+// The family the font's name table gives the face, what the page's CSS has to ask for
+fn font_family(font: &FontSource) -> Option<String> {
+    read_font_info(&font.path)
+        .ok()?
+        .into_iter()
+        .find(|face| face.index == font.index.unwrap_or(0))
+        .map(|face| face.family)
+}
+
+static BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &byte)| group | u32::from(byte) << (16 - 8 * i));
+        // A chunk of n bytes fills n + 1 sextets, the rest of the four are padding
+        for sextet in 0..4 {
+            if sextet <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * sextet) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape(c: char, text: &mut String) {
+    match c {
+        '&' => text.push_str("&amp;"),
+        '<' => text.push_str("&lt;"),
+        '>' => text.push_str("&gt;"),
+        '"' => text.push_str("&quot;"),
+        _ => text.push(c),
+    }
+}
+
+// The font itself is embedded, so the page looks the same wherever it is opened.
+// Browsers only load single fonts this way, a collection is left to whatever is installed.
+fn font_face(family: &str, font: &FontSource) -> Option<String> {
+    let mut bytes = Vec::new();
+    File::open(&font.path).and_then(|mut file| file.read_to_end(&mut bytes)).ok()?;
+    let format = match bytes.get(..4)? {
+        b"ttcf" => return None,
+        b"OTTO" => "otf",
+        _ => "ttf",
+    };
+    Some(format!("@font-face {{ font-family: \"{}\"; src: url(\"data:font/{};base64,{}\"); }}",
+                 family,
+                 format,
+                 base64(&bytes)))
+}
+
+// The @font-face rules for the primary font and its fallbacks, and the font-family list naming them.
+// Each file is embedded once and each family named once, however many of its faces are in the chain.
+// A font that cannot be read or named is left out, and the generic monospace stands in for it.
+fn font_stack(fonts: &[FontSource]) -> (String, String) {
+    let mut faces = Vec::new();
+    let mut families = Vec::new();
+    let mut embedded: Vec<&Path> = Vec::new();
+    for font in fonts {
+        let family = match font_family(font) {
+            Some(family) => family,
+            None => continue,
+        };
+        if !embedded.contains(&font.path.as_path()) {
+            embedded.push(&font.path);
+            faces.extend(font_face(&family, font));
+        }
+        if !families.contains(&family) {
+            families.push(family);
+        }
+    }
+
+    let mut stack: Vec<String> = families.iter().map(|family| format!("\"{}\"", family)).collect();
+    stack.push(String::from("monospace"));
+    (faces.join("\n"), stack.join(", "))
+}
+
+// Runs of adjacent cells in a row that share a key, as (start, cells)
+fn runs<K, F>(row: &[Cell], key: F) -> Vec<(usize, &[Cell])>
+    where K: PartialEq, F: Fn(&Cell) -> K
+{
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for end in 1..row.len() + 1 {
+        if end == row.len() || key(&row[end]) != key(&row[start]) {
+            runs.push((start, &row[start..end]));
+            start = end;
+        }
+    }
+    runs
+}
+
+pub fn to_html(art: &AsciiArt, fonts: &[FontSource]) -> String {
+    let (faces, families) = font_stack(fonts);
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n");
+    html.push_str(&faces);
+    html.push_str(&format!(
+        "\npre {{ font-family: {}; line-height: 1; }}\n</style>\n</head>\n<body>\n<pre>",
+        families
+    ));
+
+    for row in art.cells.chunks(art.columns as usize) {
+        for (_, run) in runs(row, |cell| (cell.foreground, cell.background)) {
+            html.push_str(&format!("<span style=\"color:{};background:{}\">",
+                                   hex(run[0].foreground),
+                                   hex(run[0].background)));
+            for cell in run {
+                escape(cell.c, &mut html);
+            }
+            html.push_str("</span>");
+        }
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+pub fn to_svg(art: &AsciiArt, fonts: &[FontSource], ratio: f32) -> String {
+    let (faces, families) = font_stack(fonts);
+    let cell_width = SVG_CELL_WIDTH;
+    let cell_height = SVG_CELL_WIDTH * ratio;
+
+    let mut svg = String::new();
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        art.columns as f32 * cell_width,
+        art.rows as f32 * cell_height
+    ));
+    svg.push_str(&format!("<style>{} text {{ font-family: {}; font-size: {}px; }}</style>\n",
+                          faces,
+                          families,
+                          cell_height));
+
+    for (y, row) in art.cells.chunks(art.columns as usize).enumerate() {
+        let top = y as f32 * cell_height;
+
+        for (start, run) in runs(row, |cell| cell.background) {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                start as f32 * cell_width,
+                top,
+                run.len() as f32 * cell_width,
+                cell_height,
+                hex(run[0].background)
+            ));
+        }
+
+        // textLength pins every run to the grid whatever the font's advance is
+        for (start, run) in runs(row, |cell| cell.foreground) {
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\" \
+                 fill=\"{}\" xml:space=\"preserve\">",
+                start as f32 * cell_width,
+                top + cell_height * 0.8,
+                run.len() as f32 * cell_width,
+                hex(run[0].foreground)
+            ));
+            for cell in run {
+                escape(cell.c, &mut svg);
+            }
+            svg.push_str("</text>\n");
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn export(path: &Path, contents: &str) -> Result<()> {
+    File::create(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|error| TraciiError::Export { path: path.to_path_buf(), error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use convert::Cell;
+    use tempdir::TempDir;
+    use tests::{collection, fixture};
+
+    static BLACK: [u8; 3] = [0, 0, 0];
+    static RED: [u8; 3] = [255, 0, 0];
+    static GREEN: [u8; 3] = [0, 255, 0];
+    static BLUE: [u8; 3] = [0, 0, 255];
+
+    fn art() -> AsciiArt {
+        let cell = |c, foreground, background| Cell {
+            c,
+            foreground,
+            background,
+            render: None,
+        };
+        AsciiArt {
+            columns: 3,
+            rows: 2,
+            cells: vec![
+                cell('<', RED, BLACK),
+                cell('&', RED, BLUE),
+                cell('>', GREEN, BLUE),
+                cell('"', BLUE, BLACK),
+                cell(' ', BLUE, BLACK),
+                cell('x', BLUE, BLACK),
+            ],
+        }
+    }
+
+    // The (char, foreground, background) of each cell
+    fn grid(art: &AsciiArt) -> Vec<(char, [u8; 3], [u8; 3])> {
+        art.cells.iter().map(|cell| (cell.c, cell.foreground, cell.background)).collect()
+    }
+
+    fn fonts() -> Vec<FontSource> {
+        vec![FontSource {
+            path: fixture("DejaVuSansMono.ttf"),
+            index: None,
+        }]
+    }
+
+    // What is between each start and the end after it
+    fn between<'a>(text: &'a str, start: &str, end: &str) -> Vec<&'a str> {
+        text.split(start)
+            .skip(1)
+            .filter_map(|rest| rest.split(end).next())
+            .collect()
+    }
+
+    // The value of a tag's attribute
+    fn attribute<'a>(tag: &'a str, name: &str) -> &'a str {
+        between(tag, &format!(" {}=\"", name), "\"")[0]
+    }
+
+    fn number(tag: &str, name: &str) -> f32 {
+        attribute(tag, name).parse().unwrap()
+    }
+
+    fn color(hex: &str) -> [u8; 3] {
+        let value = u32::from_str_radix(hex.trim_start_matches('#'), 16).unwrap();
+        [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+    }
+
+    fn unescape(text: &str) -> String {
+        text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&amp;", "&")
+    }
+
+    fn unbase64(encoded: &str) -> Vec<u8> {
+        let sextets: Vec<u32> = encoded
+            .bytes()
+            .filter(|&byte| byte != b'=')
+            .map(|byte| BASE64_ALPHABET.iter().position(|&a| a == byte).unwrap() as u32)
+            .collect();
+        let mut bytes = Vec::new();
+        for chunk in sextets.chunks(4) {
+            let group = chunk.iter().enumerate().fold(0, |group, (i, &sextet)| group | sextet << (18 - 6 * i));
+            for i in 0..chunk.len() - 1 {
+                bytes.push((group >> (16 - 8 * i)) as u8);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+
+    // Every span of a line is a run of cells in its colors
+    #[test]
+    fn html_round_trips_the_grid() {
+        let html = to_html(&art(), &fonts());
+
+        let pre = between(&html, "<pre>", "</pre>")[0];
+        let mut cells = Vec::new();
+        for line in pre.lines() {
+            for span in between(line, "<span ", "</span>") {
+                let (tag, text) = span.split_once('>').unwrap();
+                let foreground = color(between(tag, "color:", ";")[0]);
+                let background = color(between(tag, "background:", "\"")[0]);
+                cells.extend(unescape(text).chars().map(|c| (c, foreground, background)));
+            }
+        }
+        assert_eq!(pre.lines().count(), 2);
+        assert_eq!(cells, grid(&art()));
+    }
+
+    #[test]
+    fn html_embeds_the_font() {
+        let html = to_html(&art(), &fonts());
+        assert!(html.contains("font-family: \"DejaVu Sans Mono\", monospace;"));
+
+        let embedded = between(&html, "data:font/ttf;base64,", "\"");
+        assert_eq!(embedded.len(), 1);
+        let mut bytes = Vec::new();
+        File::open(fixture("DejaVuSansMono.ttf")).unwrap().read_to_end(&mut bytes).unwrap();
+        assert!(unbase64(embedded[0]) == bytes);
+        assert!(!html.contains("file://"));
+    }
+
+    // Each rect is a run of backgrounds and each text a run of foregrounds, placed by x and y
+    #[test]
+    fn svg_round_trips_the_grid() {
+        let (cell_width, cell_height) = (SVG_CELL_WIDTH, SVG_CELL_WIDTH * 2.0);
+        let svg = to_svg(&art(), &fonts(), 2.0);
+        let mut cells = vec![(' ', BLACK, [1, 2, 3]); 6];
+        let index = |x: f32, row: f32| (row * 3.0 + x / cell_width) as usize;
+
+        for rect in between(&svg, "<rect", "/>") {
+            let (x, y) = (number(rect, "x"), number(rect, "y"));
+            assert_eq!(number(rect, "height"), cell_height);
+            let length = (number(rect, "width") / cell_width) as usize;
+            for cell in &mut cells[index(x, y / cell_height)..][..length] {
+                cell.2 = color(attribute(rect, "fill"));
+            }
+        }
+
+        for text in between(&svg, "<text", "</text>") {
+            let (tag, run) = text.split_once('>').unwrap();
+            let run = unescape(run);
+            let row = (number(tag, "y") / cell_height).floor();
+            assert_eq!(number(tag, "textLength"), run.chars().count() as f32 * cell_width);
+            let start = index(number(tag, "x"), row);
+            for (cell, c) in cells[start..].iter_mut().zip(run.chars()) {
+                cell.0 = c;
+                cell.1 = color(attribute(tag, "fill"));
+            }
+        }
+
+        assert_eq!(cells, grid(&art()));
+        assert_eq!(number(&svg, "width"), 3.0 * cell_width);
+        assert_eq!(number(&svg, "height"), 2.0 * cell_height);
+    }
+
+    #[test]
+    fn fonts_are_embedded_once_a_file() {
+        let dir = TempDir::new("tracii").unwrap();
+        let path = dir.path().join("mono.ttc");
+        collection(&fixture("DejaVuSansMono.ttf"), 2, &path);
+
+        // The primary font twice, then both faces of a collection of it
+        let mut chain = fonts();
+        chain.extend(fonts());
+        chain.extend(FontSource::faces(&path).unwrap());
+        let html = to_html(&art(), &chain);
+
+        // Browsers cannot load the collection, so only the primary's file is embedded
+        assert_eq!(html.matches("@font-face").count(), 1);
+        assert_eq!(html.matches(";base64,").count(), 1);
+        assert!(html.contains("font-family: \"DejaVu Sans Mono\", monospace;"));
+
+        let html = to_html(&art(), &FontSource::faces(&path).unwrap());
+        assert!(!html.contains("@font-face"));
+        assert!(html.contains("font-family: \"DejaVu Sans Mono\", monospace;"));
+    }
+
+    #[test]
+    fn unreadable_fonts_fall_back_to_monospace() {
+        let missing = vec![FontSource {
+            path: fixture("missing.ttf"),
+            index: None,
+        }];
+        let html = to_html(&art(), &missing);
+        assert!(!html.contains("@font-face"));
+        assert!(html.contains("font-family: monospace;"));
+    }
+}
@@ -0,0 +1,27 @@
+extern crate tracii;
+
+use std::path::{Path, PathBuf};
+use tracii::{FontSource, Overflow};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+// Everything between a font file and its masks is reachable from outside the crate
+#[test]
+fn glyphs_render_through_the_public_api() {
+    let fonts = vec![FontSource {
+        path: fixture("DejaVuSansMono.ttf"),
+        index: None,
+    }];
+    let chars = vec!['A', 'g', ' '];
+
+    let metrics = tracii::cell_metrics(&fonts[0]).unwrap();
+    let glyphs = tracii::load_glyphs(&fonts, &chars).unwrap();
+    let masks = tracii::render_glyphs(&glyphs, &metrics, (12, 24), Overflow::Clip).unwrap();
+
+    let rendered: Vec<char> = masks.iter().map(|mask| mask.c).collect();
+    assert_eq!(rendered, chars);
+    assert!(masks.iter().all(|mask| (mask.width, mask.height) == (12, 24)));
+    assert!(tracii::uncovered_chars(&chars, &masks).is_empty());
+}
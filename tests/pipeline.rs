@@ -0,0 +1,214 @@
+extern crate tempdir;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempdir::TempDir;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+// Run tracii with the fixture font, its work and cache dirs inside dir
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tracii"))
+        .arg("--fontfile")
+        .arg(fixture("DejaVuSansMono.ttf"))
+        .arg("--workdir")
+        .arg(dir.join("work"))
+        .arg("--cachedir")
+        .arg(dir.join("cache"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn tracii(dir: &Path, args: &[&str]) -> Output {
+    let output = run(dir, args);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+fn work_dir(dir: &TempDir) -> PathBuf {
+    let work_dir = dir.path().join("work");
+    fs::create_dir(&work_dir).unwrap();
+    work_dir
+}
+
+// The names in the work dir, sorted
+fn artifacts(work_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(work_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn no_flags_make_nothing() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+    let output = tracii(dir.path(), &[]);
+
+    assert!(artifacts(&work_dir).is_empty());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Nothing to do"));
+}
+
+#[test]
+fn exportglyphs_writes_a_render_a_char() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+    tracii(dir.path(), &["--exportglyphs", "--charset", "custom:AB"]);
+
+    assert_eq!(artifacts(&work_dir), vec!["glyph_renders"]);
+    assert_eq!(artifacts(&work_dir.join("glyph_renders")), vec!["U+0041.png", "U+0042.png"]);
+}
+
+#[test]
+fn makerenderscramble_writes_a_sheet() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+    tracii(dir.path(), &["--makerenderscramble"]);
+
+    assert_eq!(artifacts(&work_dir), vec!["scramble.png"]);
+}
+
+#[test]
+fn input_writes_the_art_and_its_preview() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+    let disc = fixture("disc.png");
+    let output = tracii(dir.path(), &["--input", disc.to_str().unwrap(), "--columns", "16"]);
+
+    assert_eq!(artifacts(&work_dir), vec!["ascii.txt", "preview.png"]);
+    let text = fs::read_to_string(work_dir.join("ascii.txt")).unwrap();
+    assert!(text.lines().all(|line| line.chars().count() == 16));
+
+    // The work dir is announced once, after everything else
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("Work dir").count(), 1);
+    assert!(stdout.trim_end().ends_with(&format!("Work dir: {}", work_dir.to_string_lossy())));
+}
+
+#[test]
+fn a_seed_always_makes_the_same_sheet() {
+    let sheet = |seed| {
+        let dir = TempDir::new("tracii").unwrap();
+        let work_dir = work_dir(&dir);
+        tracii(dir.path(), &["--makerenderscramble", "--seed", seed]);
+        fs::read(work_dir.join("scramble.png")).unwrap()
+    };
+
+    assert_eq!(sheet("7"), sheet("7"));
+    assert_ne!(sheet("7"), sheet("8"));
+}
+
+#[test]
+fn cell_ratios_must_be_finite_and_positive() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+    let disc = fixture("disc.png");
+
+    for ratio in &["0", "-2", "NaN", "inf"] {
+        let flag = format!("--cellratio={}", ratio);
+        let output = run(dir.path(), &["--input", disc.to_str().unwrap(), &flag]);
+        assert_eq!(output.status.code(), Some(1), "{}", ratio);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--cellratio"));
+    }
+    assert!(artifacts(&work_dir).is_empty());
+
+    tracii(dir.path(), &["--input", disc.to_str().unwrap(), "--cellratio", "1.5"]);
+}
+
+// The pairs a run sweeps, from what it prints
+fn swept(dir: &Path, args: &[&str]) -> usize {
+    let mut args = args.to_vec();
+    args.push("--256color");
+    let stdout = String::from_utf8(tracii(dir, &args).stdout).unwrap();
+    let line = stdout.lines().find(|line| line.starts_with("Sweeping")).unwrap();
+    line.split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn palette_flags_pick_the_swept_colors() {
+    let dir = TempDir::new("tracii").unwrap();
+    work_dir(&dir);
+
+    // Both ends of a range are swept, 250 to 255 are six grays
+    assert_eq!(swept(dir.path(), &["--palette-range", "250-255"]), 6 * 5);
+    assert_eq!(swept(dir.path(), &["--palette-indices", "0,16,15,231"]), 2);
+    assert_eq!(swept(dir.path(), &["--palette-indices", "0,8,15", "--min-contrast", "4.5"]), 4);
+
+    for args in &[
+        &["--palette-range", "5-3"][..],
+        &["--palette-range", "5"][..],
+        &["--palette-range", "0-3", "--palette-indices", "0"][..],
+        &["--palette-indices", "0,256"][..],
+        &["--palette-indices", "0,15", "--min-contrast", "22"][..],
+    ] {
+        let mut args = args.to_vec();
+        args.push("--256color");
+        assert_eq!(run(dir.path(), &args).status.code(), Some(1), "{:?}", args);
+    }
+}
+
+#[test]
+fn stages_combine_into_one_work_dir() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+    let disc = fixture("disc.png");
+    tracii(dir.path(), &[
+        "--charset",
+        "custom:AB",
+        "--exportglyphs",
+        "--makerenderscramble",
+        "--input",
+        disc.to_str().unwrap(),
+        "--columns",
+        "16",
+        "--exporthtml",
+        "--exportsvg",
+    ]);
+
+    assert_eq!(
+        artifacts(&work_dir),
+        vec!["ascii.html", "ascii.svg", "ascii.txt", "glyph_renders", "preview.png", "scramble.png"]
+    );
+    assert_eq!(artifacts(&work_dir.join("glyph_renders")), vec!["U+0041.png", "U+0042.png"]);
+
+    let html = fs::read_to_string(work_dir.join("ascii.html")).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>") && html.contains("<pre>"));
+    let svg = fs::read_to_string(work_dir.join("ascii.svg")).unwrap();
+    assert!(svg.starts_with("<svg") && svg.contains("<text"));
+}
+
+#[test]
+fn conversion_flags_need_an_input() {
+    let dir = TempDir::new("tracii").unwrap();
+    let work_dir = work_dir(&dir);
+
+    for args in &[
+        &["--exporthtml"][..],
+        &["--exportsvg"][..],
+        &["--output-format", "ansi16"][..],
+        &["--metric", "ssim"][..],
+        &["--indexed"][..],
+        &["--rows", "10"][..],
+        &["--fit"][..],
+        &["--size-policy", "crop"][..],
+        &["--columns", "10"][..],
+        &["--columns", "10", "--exportglyphs"][..],
+        &["--mode", "braille"][..],
+    ] {
+        let output = run(dir.path(), args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--input"), "{:?}", args);
+    }
+    assert!(artifacts(&work_dir).is_empty());
+
+    // Columns size the benchmark's grid and the mode picks the atlas's chars
+    tracii(dir.path(), &["--columns", "10", "--benchindex"]);
+    tracii(dir.path(), &["--mode", "halfblock", "--exportglyphs"]);
+    assert_eq!(artifacts(&work_dir.join("glyph_renders")), vec!["U+2580.png", "U+2584.png"]);
+}